@@ -0,0 +1,376 @@
+use std::fmt;
+use std::fs;
+use std::time;
+
+use crate::pack::Pack;
+use crate::solve::Budget;
+use crate::{progress, validate, TICK_TIME};
+
+/// Exit code when everything went to plan
+pub const EXIT_SUCCESS: i32 = 0;
+
+/// Exit code when a level couldn't be loaded, or failed its checks
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code when the command line itself doesn't make sense
+pub const EXIT_USAGE: i32 = 2;
+
+const SUBCOMMANDS: [&str; 5] = ["play", "edit", "check", "solve", "replay"];
+
+/// Settings that can be given alongside any subcommand
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub tick_time: time::Duration,
+    /// Level to start on, counted from 1
+    pub level: Option<usize>,
+    /// Name of the pack to play
+    pub pack: Option<String>,
+    pub seed: Option<u64>,
+    pub colour: bool,
+    /// File to write a replay of each level played to
+    pub record: Option<String>,
+    /// How hard `solve` looks for a solution
+    pub budget: Budget,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            tick_time: TICK_TIME,
+            level: None,
+            pack: None,
+            seed: None,
+            colour: true,
+            record: None,
+            budget: Budget::default(),
+        }
+    }
+}
+
+/// What the player asked crabs to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Play the built-in and installed levels, or the given level files and packs
+    Play(Vec<String>),
+    /// Create a new level, saving it to the given file
+    Edit(Option<String>),
+    /// Validate level files
+    Check(Vec<String>),
+    /// Search for the cheapest way to win the given levels (or the built-in and installed ones)
+    Solve(Vec<String>),
+    /// Watch a recorded session
+    Replay(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub options: Options,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    /// Help was asked for, rather than anything going wrong
+    Help,
+    Version,
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+    },
+    /// Option that doesn't take a value was given one
+    UnexpectedValue(String),
+    /// Subcommand needs a file that wasn't given
+    MissingFile(&'static str),
+    UnexpectedArgument(String),
+}
+
+impl CliError {
+    /// Exit code for the process once the error has been reported
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Help | CliError::Version => EXIT_SUCCESS,
+            _ => EXIT_USAGE,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Help => write!(f, "help requested"),
+            CliError::Version => write!(f, "version requested"),
+            CliError::UnknownOption(option) => write!(f, "unknown option {}", option),
+            CliError::MissingValue(option) => write!(f, "{} needs a value", option),
+            CliError::InvalidValue { option, value } => {
+                write!(f, "{} is not a valid value for {}", value, option)
+            }
+            CliError::UnexpectedValue(option) => write!(f, "{} doesn't take a value", option),
+            CliError::MissingFile(command) => write!(f, "{} needs a level file", command),
+            CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument {}", arg),
+        }
+    }
+}
+
+impl Cli {
+    /// Parse the arguments following the executable name.
+    ///
+    /// Options may appear before or after the subcommand, either as `--option value`
+    /// or `--option=value`. Without a subcommand the remaining arguments are played.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut options = Options::default();
+        let mut subcommand: Option<String> = None;
+        let mut files: Vec<String> = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Split `--option=value` so both spellings are handled alike
+            let (option, inline) = match arg.find('=') {
+                Some(split) if arg.starts_with("--") => {
+                    (arg[..split].to_string(), Some(arg[split + 1..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let value = |args: &mut I::IntoIter| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::MissingValue(option.clone()))
+            };
+
+            match option.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                "-V" | "--version" => return Err(CliError::Version),
+                "--tick-time" => {
+                    let millis = number(&option, value(&mut args)?)?;
+                    options.tick_time = time::Duration::from_millis(millis);
+                }
+                "--level" => match number(&option, value(&mut args)?)? {
+                    0 => {
+                        return Err(CliError::InvalidValue {
+                            option,
+                            value: String::from("0"),
+                        })
+                    }
+                    level => options.level = Some(level as usize),
+                },
+                "--pack" => options.pack = Some(value(&mut args)?),
+                "--record" => options.record = Some(value(&mut args)?),
+                "--max-placements" => {
+                    options.budget.placements = number(&option, value(&mut args)?)? as usize
+                }
+                "--max-ticks" => {
+                    options.budget.ticks = number(&option, value(&mut args)?)? as usize
+                }
+                "--max-attempts" => {
+                    options.budget.attempts = number(&option, value(&mut args)?)? as usize
+                }
+                "--seed" => options.seed = Some(number(&option, value(&mut args)?)?),
+                "--no-color" | "--no-colour" => {
+                    if inline.is_some() {
+                        return Err(CliError::UnexpectedValue(option));
+                    }
+                    options.colour = false;
+                }
+                "--" => files.extend(args.by_ref()),
+                _ if option.starts_with('-') && option.len() > 1 => {
+                    return Err(CliError::UnknownOption(option))
+                }
+                _ if subcommand.is_none()
+                    && files.is_empty()
+                    && SUBCOMMANDS.contains(&arg.as_str()) =>
+                {
+                    subcommand = Some(arg)
+                }
+                _ => files.push(arg),
+            }
+        }
+
+        let command = match subcommand.as_deref() {
+            None | Some("play") => Command::Play(files),
+            Some("edit") => Command::Edit(at_most_one(files)?),
+            Some("check") if files.is_empty() => return Err(CliError::MissingFile("check")),
+            Some("check") => Command::Check(files),
+            Some("solve") => Command::Solve(files),
+            Some(_) => Command::Replay(exactly_one("replay", files)?),
+        };
+        Ok(Cli { options, command })
+    }
+}
+
+fn number(option: &str, value: String) -> Result<u64, CliError> {
+    value.parse().map_err(|_| CliError::InvalidValue {
+        option: option.to_string(),
+        value,
+    })
+}
+
+fn at_most_one(files: Vec<String>) -> Result<Option<String>, CliError> {
+    let mut files = files.into_iter();
+    let first = files.next();
+    match files.next() {
+        Some(extra) => Err(CliError::UnexpectedArgument(extra)),
+        None => Ok(first),
+    }
+}
+
+fn exactly_one(command: &'static str, files: Vec<String>) -> Result<String, CliError> {
+    at_most_one(files)?.ok_or(CliError::MissingFile(command))
+}
+
+pub fn print_usage() {
+    eprintln!("usage: crabs [OPTIONS] [play] [LEVEL_OR_PACK...]");
+    eprintln!("       crabs [OPTIONS] edit [FILE]");
+    eprintln!("       crabs check FILE...");
+    eprintln!("       crabs [OPTIONS] solve [LEVEL_OR_PACK...]");
+    eprintln!("       crabs [OPTIONS] replay FILE");
+    eprintln!("Try crabs --help for more information.");
+}
+
+pub fn print_help() {
+    println!("\ncrabs --help");
+    println!("------------");
+    println!("Guide the crabs to safety:");
+    println!("\t. crab");
+    println!("\tO heavy crab, breaks trampolines it lands on");
+    println!("\tY climber, climbs walls one block high");
+    println!("\t* floater, falls slowly");
+    println!("\tK king, must reach safety");
+    println!("\tX safety (exits may only take so many crabs, or one team)");
+    println!("\tH hatch, lets crabs out over time");
+    println!("Work the machinery:");
+    println!("\t_ pressure plate, switches doors while a crab stands on it");
+    println!("\tD door, shut (d when open)");
+    println!("\t}} conveyor belt, carries crabs forward ({{ backward)");
+    println!("\t) one-way gate, only lets crabs through forward (( backward)");
+    println!("Avoid hazards and long falls:");
+    println!("\t^ spikes");
+    println!("\t~ water");
+    println!("\t% lava");
+    println!("Use the arrow keys to move the cursor:");
+    println!("\t+ cursor");
+    println!("Insert scenery by typing the appropriate key:");
+    println!("\t# block");
+    println!("\t/ forward ramp");
+    println!("\t\\ backward ramp");
+    println!("\t@ trampoline");
+    println!("\t> forward boost");
+    println!("\t< backward boost");
+    println!("\tT teleporter, pairs up with the next one placed");
+    println!("Type u to undo a placement and Ctrl-R to redo it, or Delete");
+    println!("to take back scenery you placed (underlined) under the cursor.");
+    println!("Give the crab under the cursor a job by typing its number:");
+    println!("\t1 blocker, turns back other crabs");
+    println!("\t2 digger, digs down through blocks");
+    println!("\t3 builder, lays a staircase of ramps");
+    println!("\t4 turn around");
+    println!("Some levels limit how much scenery can be placed,");
+    println!("the remaining counts are shown below the map.");
+    println!("Each level, type r to restart and q to quit.");
+    println!("Choose a level with the arrow keys and Enter, q to exit.");
+    println!("Progress is saved in $XDG_DATA_HOME/crabs, and packs");
+    println!("installed in $XDG_DATA_HOME/crabs/packs can be played too.\n");
+    println!("Subcommands:");
+    println!("\tcrabs [play] [LEVEL_OR_PACK...]  play levels (the default)");
+    println!("\tcrabs edit [FILE]                create or edit a level");
+    println!("\tcrabs check FILE...              check level files for mistakes");
+    println!("\tcrabs solve [LEVEL_OR_PACK...]   find the fewest pre-placements that win");
+    println!("\tcrabs replay FILE                watch a recorded session");
+    println!("Options:");
+    println!(
+        "\t--tick-time N  milliseconds per tick, larger N makes the crabs slower (default 100)"
+    );
+    println!("\t--pack NAME    play the built-in or installed pack called NAME");
+    println!("\t--level N      start straight away on level N of the pack");
+    println!("\t--seed N       shuffle the order crabs move in, reproducibly");
+    println!("\t--no-color     don't colour the map");
+    println!("\t--record FILE  save a replay of each level played, the first to FILE");
+    println!("\t               and later ones to FILE-2, FILE-3 and so on");
+    println!("\t--max-placements N, --max-ticks N, --max-attempts N");
+    println!("\t               limit how far solve searches (default 3, 1000, 200000)");
+    println!("\t--help         show this message");
+    println!("\t--version      show the version number");
+    println!("Whilst watching a replay, type space to pause, n to step");
+    println!("forward a tick, f to fast-forward and q to quit.");
+    println!("Exit status is 0 on success, 1 if a level couldn't be");
+    println!("loaded or checked, and 2 for a bad command line.\n");
+}
+
+/// Packs to play: the ones given on the command line, otherwise the built-in and installed ones,
+/// narrowed down to the one named by `--pack`
+pub fn packs(options: &Options, paths: &[String]) -> Result<Vec<Pack>, String> {
+    let packs = load_packs(paths)?;
+    match &options.pack {
+        Some(name) => select_pack(packs, name),
+        None => Ok(packs),
+    }
+}
+
+/// The level asked for by `--level`, as indices of a pack and a level within it
+pub fn start_level(options: &Options, packs: &[Pack]) -> Result<Option<(usize, usize)>, String> {
+    // Levels are counted within the first pack
+    match options.level {
+        Some(level) if level > packs[0].levels.len() => Err(format!(
+            "{} only has {} level(s)",
+            packs[0].name,
+            packs[0].levels.len()
+        )),
+        Some(level) => Ok(Some((0, level - 1))),
+        None => Ok(None),
+    }
+}
+
+fn load_packs(paths: &[String]) -> Result<Vec<Pack>, String> {
+    if paths.is_empty() {
+        let mut packs = vec![Pack::default_pack()];
+        for pack in progress::installed_packs() {
+            match pack {
+                Ok(pack) => packs.push(pack),
+                Err(err) => eprintln!("Skipping installed pack {}", err),
+            }
+        }
+        Ok(packs)
+    } else {
+        paths
+            .iter()
+            .map(|path| Pack::load(path).map_err(|err| format!("Unable to load {}", err)))
+            .collect()
+    }
+}
+
+fn select_pack(packs: Vec<Pack>, name: &str) -> Result<Vec<Pack>, String> {
+    let names: Vec<String> = packs.iter().map(|pack| pack.name.clone()).collect();
+    match packs.into_iter().find(|pack| pack.name == name) {
+        Some(pack) => Ok(vec![pack]),
+        None => Err(format!(
+            "No pack called {} (available: {})",
+            name,
+            names.join(", ")
+        )),
+    }
+}
+
+/// Check each level file, reporting problems in the style of a compiler so editors can jump to them
+pub fn check(paths: &[String]) -> bool {
+    let mut valid = true;
+    for path in paths {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                let diagnostics = validate::validate_level(&source);
+                for diagnostic in diagnostics.iter() {
+                    println!("{}:{}", path, diagnostic);
+                }
+                if diagnostics.is_empty() {
+                    println!("{}: ok", path);
+                }
+                valid &= diagnostics.is_empty();
+            }
+            Err(err) => {
+                println!("{}: unable to read level file: {}", path, err);
+                valid = false;
+            }
+        }
+    }
+    valid
+}
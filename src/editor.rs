@@ -0,0 +1,901 @@
+use std::fs;
+use std::io::{stdout, Write};
+use std::mem;
+use std::path::Path;
+use std::time;
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::{clear, cursor, style};
+
+use crate::format::{self, LevelError, LevelFile};
+use crate::{
+    check_resize, levels, prompt_for_filename, Crab, Entities, Event, Kind, Level, Outcome,
+    Scenery, Simulation, Spawner, SCALE, VMAX,
+};
+
+/// What the arrow keys do in the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Move the cursor
+    Paint,
+    /// Change the velocity of the crab or hatch under the cursor
+    Steer,
+}
+
+/// Way of painting many cells at once, waiting for the glyph to paint with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// Every cell in the selected box
+    Fill,
+    /// The edges of the selected box
+    Outline,
+    /// A straight line from the selection's first corner to the cursor
+    Line,
+    /// The cell under the cursor and every cell joined to it with the same scenery
+    Flood,
+}
+
+/// Something the editor needs whoever is running it to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Save,
+    /// Save, then stop editing
+    Quit,
+}
+
+/// A level being edited, along with the cursor.
+///
+/// Typing a scenery glyph paints it under the cursor (a space clears the cell), and typing a
+/// crab glyph puts a crab of that kind there, ready to be steered with the arrow keys. A box
+/// between a marked corner and the cursor can be filled, outlined or copied. Every change can
+/// be undone, and the level can be play-tested without leaving the editor.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    level: Level,
+    // The level before each change, oldest first, then as it was before each undo
+    history: Vec<Level>,
+    undone: Vec<Level>,
+    cursor: [usize; 2],
+    mode: Mode,
+    // Corner of the selected box (the cursor is the other)
+    anchor: Option<[usize; 2]>,
+    tool: Option<Tool>,
+    // Copied scenery, row by row
+    clipboard: Vec<Vec<Scenery>>,
+    // Copy of the level being play-tested
+    trial: Option<Simulation>,
+    paused: bool,
+    // Feedback on the last key press
+    message: String,
+}
+
+impl Editor {
+    pub fn new(level: Level) -> Self {
+        let mut level = level;
+        let dropped = fit(&mut level);
+        let dimensions = level.map.dimensions;
+        Editor {
+            level,
+            history: Vec::new(),
+            undone: Vec::new(),
+            cursor: [dimensions[0] / 2, dimensions[1] / 2],
+            mode: Mode::Paint,
+            anchor: None,
+            tool: None,
+            clipboard: Vec::new(),
+            trial: None,
+            paused: false,
+            message: match dropped {
+                0 => String::new(),
+                n => format!("Left out {} things outside the layout", n),
+            },
+        }
+    }
+
+    /// An empty map with no crabs
+    pub fn blank() -> Self {
+        Editor::new(Level::new(
+            Entities::new(Vec::new(), Vec::new()),
+            levels::blank_map(),
+        ))
+    }
+
+    /// Edit a level file (any problems with it are left for the designer to fix, apart from
+    /// things beyond the edges of the layout, which are left out)
+    pub fn open(path: &str) -> Result<Self, LevelError> {
+        let source = fs::read_to_string(path)?;
+        Ok(Editor::new(LevelFile::parse(&source)?.into_level()))
+    }
+
+    /// Respond to a key press, changing the level or moving the cursor
+    pub fn press(&mut self, key: Key) -> Option<Request> {
+        self.message.clear();
+        // Play-testing leaves the level alone
+        if self.trial.is_some() {
+            match key {
+                Key::Char('q') => return Some(Request::Quit),
+                Key::Char('w') => return Some(Request::Save),
+                Key::Char('t') | Key::Esc => self.trial = None,
+                Key::Char(' ') => self.paused = !self.paused,
+                Key::Char('n') if self.paused => {
+                    self.advance();
+                }
+                _ => (),
+            }
+            return None;
+        }
+
+        match (self.mode, key) {
+            (_, Key::Char('q')) => return Some(Request::Quit),
+            (_, Key::Char('w')) => return Some(Request::Save),
+            (_, Key::Char('t')) => {
+                self.trial = Some(Simulation::new(self.level.clone()));
+                self.paused = false;
+            }
+            (_, Key::Esc) => {
+                self.anchor = None;
+                self.tool = None;
+            }
+            (Mode::Paint, Key::Char('v')) => self.mode = Mode::Steer,
+            (Mode::Steer, Key::Char('v')) | (Mode::Steer, Key::Char('\n')) => {
+                self.mode = Mode::Paint
+            }
+            (_, Key::Char('u')) => self.undo(),
+            (_, Key::Ctrl('r')) => self.redo(),
+            (_, Key::Delete) | (_, Key::Backspace) => self.delete(),
+            (_, Key::Char('+')) => self.count(1),
+            (_, Key::Char('-')) => self.count(-1),
+            (_, Key::Char('s')) => {
+                self.anchor = Some(self.cursor);
+                self.message = String::from("Move the cursor to the opposite corner");
+            }
+            (_, Key::Char('b')) => self.choose(Tool::Fill),
+            (_, Key::Char('o')) => self.choose(Tool::Outline),
+            (_, Key::Char('l')) => self.choose(Tool::Line),
+            (_, Key::Char('f')) => self.choose(Tool::Flood),
+            (_, Key::Char('c')) => self.copy(),
+            (_, Key::Char('p')) => self.paste(),
+            (_, Key::Char('r')) => self.turn(),
+            (_, Key::Char('m')) => self.mirror(),
+            (_, Key::Char(glyph)) => {
+                if let Some(kind) = Kind::from_char(glyph) {
+                    self.place(kind);
+                } else if let Some(scenery) = Scenery::from_char(glyph) {
+                    let cells = match self.tool.take() {
+                        Some(tool) => self.cells(tool),
+                        None => vec![self.cursor],
+                    };
+                    self.paint(cells.into_iter().map(|cell| (cell, scenery)).collect());
+                }
+            }
+            (mode, key) => {
+                let change = match key {
+                    Key::Left => [0, -1],
+                    Key::Right => [0, 1],
+                    Key::Up => [-1, 0],
+                    Key::Down => [1, 0],
+                    _ => return None,
+                };
+                match mode {
+                    Mode::Paint => self.level.map.nudge(&mut self.cursor, change),
+                    Mode::Steer => self.steer(change),
+                }
+            }
+        }
+        None
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn cursor(&self) -> [usize; 2] {
+        self.cursor
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Play-test of the level, if one is under way
+    pub fn trial(&self) -> Option<&Simulation> {
+        self.trial.as_ref()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advance the play-test by a tick, unless it is paused
+    pub fn tick(&mut self) -> Vec<Event> {
+        if self.paused {
+            Vec::new()
+        } else {
+            self.advance()
+        }
+    }
+
+    fn advance(&mut self) -> Vec<Event> {
+        match self.trial.as_mut() {
+            Some(simulation) => simulation.step(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Tool waiting for a glyph to paint with
+    pub fn tool(&self) -> Option<Tool> {
+        self.tool
+    }
+
+    /// Top left and bottom right corners of the selected box
+    pub fn selection(&self) -> Option<[[usize; 2]; 2]> {
+        self.anchor.map(|anchor| {
+            let cursor = self.cursor;
+            [
+                [anchor[0].min(cursor[0]), anchor[1].min(cursor[1])],
+                [anchor[0].max(cursor[0]), anchor[1].max(cursor[1])],
+            ]
+        })
+    }
+
+    /// Feedback on the last key press
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Describe what's under the cursor
+    pub fn inspect(&self) -> String {
+        let cell = self.cursor;
+        let entities = &self.level.entities;
+        if let Some(crab) = entities.iter().find(|crab| crab.position == cell) {
+            format!("crab {} velocity {:?}", crab.kind.to_char(), crab.velocity)
+        } else if let Some(spawner) = entities.spawners.iter().find(|s| s.position == cell) {
+            format!(
+                "hatch x{} every {} velocity {:?}",
+                spawner.count, spawner.interval, spawner.velocity
+            )
+        } else {
+            format!("{:?}", self.level.map.layout[cell[0]][cell[1]])
+        }
+    }
+
+    // Paint scenery over cells, keeping anything that goes with the tiles up to date (crabs
+    // keep their cells unless they could stand in the new scenery)
+    fn paint(&mut self, tiles: Vec<([usize; 2], Scenery)>) {
+        let entities = &self.level.entities;
+        let tiles: Vec<([usize; 2], Scenery)> = tiles
+            .into_iter()
+            .filter(|&(cell, scenery)| {
+                scenery.is_passable() || entities.iter().all(|crab| crab.position != cell)
+            })
+            .collect();
+        if tiles.is_empty() {
+            self.message = String::from("A crab is in the way");
+            return;
+        }
+        self.checkpoint();
+
+        let before = self.level.entities.len() + self.level.entities.pending();
+        for (cell, scenery) in tiles {
+            let map = &mut self.level.map;
+            map.overide(&cell, scenery);
+            if scenery == Scenery::Teleporter
+                && map.partner(cell).is_none()
+                && map.unpaired != Some(cell)
+            {
+                map.pair(cell);
+            }
+            let spawners = &mut self.level.entities.spawners;
+            if scenery == Scenery::Spawner && spawners.iter().all(|s| s.position != cell) {
+                spawners.push(Spawner::new(cell, 1, 1, [0, 1], Kind::Common));
+            }
+        }
+        self.tidy();
+        self.recount(before);
+    }
+
+    // Get a tool ready, if there's a box selected for it to work on
+    fn choose(&mut self, tool: Tool) {
+        if tool != Tool::Flood && self.anchor.is_none() {
+            self.message = String::from("Press s to mark a corner first");
+            return;
+        }
+        self.tool = Some(tool);
+        self.message = String::from("Type the glyph to paint with");
+    }
+
+    // Cells a tool paints
+    fn cells(&self, tool: Tool) -> Vec<[usize; 2]> {
+        let [[top, left], [bottom, right]] = match self.selection() {
+            Some(corners) => corners,
+            None => [self.cursor, self.cursor],
+        };
+        let inside = (top..=bottom).flat_map(|y| (left..=right).map(move |x| [y, x]));
+        match tool {
+            Tool::Fill => inside.collect(),
+            Tool::Outline => inside
+                .filter(|&[y, x]| y == top || y == bottom || x == left || x == right)
+                .collect(),
+            Tool::Line => line(self.anchor.unwrap_or(self.cursor), self.cursor),
+            Tool::Flood => self.region(),
+        }
+    }
+
+    // The cell under the cursor and every cell joined to it with the same scenery
+    fn region(&self) -> Vec<[usize; 2]> {
+        let layout = &self.level.map.layout;
+        let [height, width] = self.level.map.dimensions;
+        let [y, x] = self.cursor;
+        let scenery = layout[y][x];
+
+        let mut seen = vec![vec![false; width]; height];
+        seen[y][x] = true;
+        let mut waiting = vec![self.cursor];
+        let mut region = Vec::new();
+        while let Some(cell) = waiting.pop() {
+            region.push(cell);
+            let [y, x] = cell;
+            let neighbours = [
+                [y.wrapping_sub(1), x],
+                [y + 1, x],
+                [y, x.wrapping_sub(1)],
+                [y, x + 1],
+            ];
+            for [y, x] in neighbours.iter().copied() {
+                if y < height && x < width && !seen[y][x] && layout[y][x] == scenery {
+                    seen[y][x] = true;
+                    waiting.push([y, x]);
+                }
+            }
+        }
+        region
+    }
+
+    // Copy the scenery in the selected box
+    fn copy(&mut self) {
+        match self.selection() {
+            Some([[top, left], [bottom, right]]) => {
+                let layout = &self.level.map.layout;
+                self.clipboard = (top..=bottom)
+                    .map(|y| layout[y][left..=right].to_vec())
+                    .collect();
+                self.message = format!("Copied {} by {}", bottom - top + 1, right - left + 1);
+            }
+            None => self.message = String::from("Press s to mark a corner first"),
+        }
+    }
+
+    // Paint the copied scenery with its top left corner under the cursor (anything beyond the
+    // edge of the map is left off)
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            self.message = String::from("Nothing copied yet");
+            return;
+        }
+        let [height, width] = self.level.map.dimensions;
+        let [top, left] = self.cursor;
+        let mut tiles = Vec::new();
+        for (dy, row) in self.clipboard.iter().enumerate() {
+            for (dx, &scenery) in row.iter().enumerate() {
+                let [y, x] = [top + dy, left + dx];
+                if y < height && x < width {
+                    tiles.push(([y, x], scenery));
+                }
+            }
+        }
+        self.paint(tiles);
+    }
+
+    // Turn the copied scenery a quarter clockwise
+    fn turn(&mut self) {
+        let height = self.clipboard.len();
+        let width = self.clipboard.first().map_or(0, |row| row.len());
+        self.clipboard = (0..width)
+            .map(|x| {
+                (0..height)
+                    .rev()
+                    .map(|y| turned(self.clipboard[y][x]))
+                    .collect()
+            })
+            .collect();
+    }
+
+    // Reflect the copied scenery left to right
+    fn mirror(&mut self) {
+        for row in self.clipboard.iter_mut() {
+            row.reverse();
+            for scenery in row.iter_mut() {
+                *scenery = mirrored(*scenery);
+            }
+        }
+    }
+
+    // Drop anything attached to tiles that have been painted over
+    fn tidy(&mut self) {
+        let map = &mut self.level.map;
+        let layout = &map.layout;
+        let at = |cell: [usize; 2]| layout[cell[0]][cell[1]];
+
+        map.exits
+            .retain(|exit| at(exit.position) == Scenery::Safety);
+        map.links.retain(|link| at(link.plate) == Scenery::Plate);
+        for link in map.links.iter_mut() {
+            link.doors
+                .retain(|&door| matches!(at(door), Scenery::Door { .. }));
+        }
+        self.level
+            .entities
+            .spawners
+            .retain(|spawner| at(spawner.position) == Scenery::Spawner);
+
+        // A pad that loses its partner waits for another
+        let mut widowed = Vec::new();
+        map.teleporters.retain(|teleporter| {
+            let intact = teleporter
+                .pads
+                .iter()
+                .all(|&pad| at(pad) == Scenery::Teleporter);
+            if !intact {
+                widowed.extend(
+                    teleporter
+                        .pads
+                        .iter()
+                        .filter(|&&pad| at(pad) == Scenery::Teleporter),
+                );
+            }
+            intact
+        });
+        if map
+            .unpaired
+            .is_some_and(|pad| at(pad) != Scenery::Teleporter)
+        {
+            map.unpaired = None;
+        }
+        for pad in widowed {
+            map.pair(pad);
+        }
+    }
+
+    // Put a crab under the cursor (or change the kind of the one already there)
+    fn place(&mut self, kind: Kind) {
+        let cell = self.cursor;
+        let crab = self.level.entities.iter().any(|crab| crab.position == cell);
+        if !crab && !self.level.map.layout[cell[0]][cell[1]].is_passable() {
+            self.message = String::from("Crabs can't start inside scenery");
+            return;
+        }
+        self.checkpoint();
+
+        let before = self.level.entities.len() + self.level.entities.pending();
+        let entities = &mut self.level.entities;
+        let id = match entities.find(cell) {
+            Some(crab) => {
+                crab.kind = kind;
+                crab.id
+            }
+            None => {
+                let id = entities.created;
+                entities.collection.push(Crab::new(id, cell, [0, 0], kind));
+                entities.created += 1;
+                id
+            }
+        };
+        entities.kings.retain(|&king| king != id);
+        if kind == Kind::King {
+            entities.kings.push(id);
+        }
+        self.recount(before);
+
+        self.mode = Mode::Steer;
+        self.message = String::from("Steer the crab with the arrow keys, then press Enter");
+    }
+
+    // Change the velocity of the crab or hatch under the cursor
+    fn steer(&mut self, change: [isize; 2]) {
+        let cell = self.cursor;
+        let entities = &self.level.entities;
+        if !entities.iter().any(|crab| crab.position == cell)
+            && !entities.spawners.iter().any(|s| s.position == cell)
+        {
+            self.message = String::from("Nothing here to steer");
+            return;
+        }
+        self.checkpoint();
+
+        let entities = &mut self.level.entities;
+        let velocity = match entities.find(cell) {
+            Some(crab) => &mut crab.velocity,
+            None => match entities.spawners.iter_mut().find(|s| s.position == cell) {
+                Some(spawner) => &mut spawner.velocity,
+                None => return,
+            },
+        };
+        for axis in 0..2 {
+            velocity[axis] = (velocity[axis] + change[axis]).clamp(-VMAX, VMAX);
+        }
+        if let Some(crab) = entities.find(cell) {
+            crab.speed = [crab.velocity[0] * SCALE, crab.velocity[1] * SCALE];
+        }
+    }
+
+    // Change the number of crabs the hatch under the cursor lets out
+    fn count(&mut self, change: isize) {
+        let cell = self.cursor;
+        if self
+            .level
+            .entities
+            .spawners
+            .iter()
+            .all(|s| s.position != cell)
+        {
+            self.message = String::from("No hatch here");
+            return;
+        }
+        self.checkpoint();
+
+        let before = self.level.entities.len() + self.level.entities.pending();
+        for spawner in self.level.entities.spawners.iter_mut() {
+            if spawner.position == cell {
+                spawner.count = (spawner.count as isize + change).max(1) as usize;
+            }
+        }
+        self.recount(before);
+    }
+
+    // Remove the crab under the cursor, or failing that clear the cell
+    fn delete(&mut self) {
+        let cell = self.cursor;
+        let entities = &self.level.entities;
+        if !entities.iter().any(|crab| crab.position == cell) {
+            self.paint(vec![(cell, Scenery::Empty)]);
+            return;
+        }
+        self.checkpoint();
+
+        let before = self.level.entities.len() + self.level.entities.pending();
+        let entities = &mut self.level.entities;
+        if let Some(index) = entities
+            .collection
+            .iter()
+            .position(|crab| crab.position == cell)
+        {
+            let crab = entities.collection.remove(index);
+            entities.kings.retain(|&king| king != crab.id);
+        }
+        self.recount(before);
+    }
+
+    // Note the level as it is before a change, which rules out redoing anything
+    fn checkpoint(&mut self) {
+        self.history.push(self.level.clone());
+        self.undone.clear();
+    }
+
+    fn undo(&mut self) {
+        match self.history.pop() {
+            Some(level) => self.undone.push(mem::replace(&mut self.level, level)),
+            None => self.message = String::from("Nothing to undo"),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.undone.pop() {
+            Some(level) => self.history.push(mem::replace(&mut self.level, level)),
+            None => self.message = String::from("Nothing to redo"),
+        }
+    }
+
+    // Levels that rescue every crab keep doing so as crabs come and go
+    fn recount(&mut self, before: usize) {
+        if self.level.rescue == before {
+            self.level.rescue = self.level.entities.len() + self.level.entities.pending();
+        }
+    }
+}
+
+// Make a level safe to edit, giving it a blank map if it has no cells, slowing anything
+// faster than terminal velocity and leaving out anything placed beyond the edges of the
+// layout, returning how many things were left out
+fn fit(level: &mut Level) -> usize {
+    if level.map.dimensions.contains(&0) {
+        let map = mem::replace(&mut level.map, levels::blank_map());
+        level.map.physics = map.physics;
+        level.map.inventory = map.inventory;
+        level.map.boundaries = map.boundaries;
+    }
+    let [height, width] = level.map.dimensions;
+    let inside = |cell: &[usize; 2]| cell[0] < height && cell[1] < width;
+
+    let before = level.entities.len() + level.entities.pending();
+    let map = &mut level.map;
+    let entities = &mut level.entities;
+    let mut dropped = 0;
+    let mut keep = |kept: bool| {
+        dropped += usize::from(!kept);
+        kept
+    };
+    entities
+        .collection
+        .retain(|crab| keep(inside(&crab.position)));
+    let ids: Vec<usize> = entities.collection.iter().map(|crab| crab.id).collect();
+    entities.kings.retain(|king| ids.contains(king));
+    entities
+        .spawners
+        .retain(|spawner| keep(inside(&spawner.position)));
+    map.exits.retain(|exit| keep(inside(&exit.position)));
+    map.links.retain(|link| keep(inside(&link.plate)));
+    for link in map.links.iter_mut() {
+        link.doors.retain(|door| keep(inside(door)));
+    }
+    map.teleporters
+        .retain(|teleporter| keep(teleporter.pads.iter().all(inside)));
+    if map.unpaired.is_some_and(|pad| !inside(&pad)) {
+        map.unpaired = None;
+    }
+    let limit = |velocity: &mut [isize; 2]| {
+        for v in velocity.iter_mut() {
+            *v = (*v).clamp(-VMAX, VMAX);
+        }
+    };
+    for crab in entities.collection.iter_mut() {
+        limit(&mut crab.velocity);
+        crab.speed = [crab.velocity[0] * SCALE, crab.velocity[1] * SCALE];
+    }
+    for spawner in entities.spawners.iter_mut() {
+        limit(&mut spawner.velocity);
+    }
+
+    if level.rescue == before {
+        level.rescue = level.entities.len() + level.entities.pending();
+    }
+    dropped
+}
+
+// Cells on the straight line between two cells, from the first
+fn line(from: [usize; 2], to: [usize; 2]) -> Vec<[usize; 2]> {
+    let change = [
+        to[0] as isize - from[0] as isize,
+        to[1] as isize - from[1] as isize,
+    ];
+    let steps = change[0].abs().max(change[1].abs());
+    (0..=steps)
+        .map(|step| {
+            let along = |axis: usize| {
+                let offset = change[axis] as f64 * step as f64 / steps.max(1) as f64;
+                (from[axis] as isize + offset.round() as isize) as usize
+            };
+            [along(0), along(1)]
+        })
+        .collect()
+}
+
+// Tile reflected left to right
+fn mirrored(scenery: Scenery) -> Scenery {
+    match scenery {
+        Scenery::ForwardWedge => Scenery::BackwardWedge,
+        Scenery::BackwardWedge => Scenery::ForwardWedge,
+        Scenery::ForwardBoost => Scenery::BackwardBoost,
+        Scenery::BackwardBoost => Scenery::ForwardBoost,
+        Scenery::Conveyor { forward } => Scenery::Conveyor { forward: !forward },
+        Scenery::Gate { forward } => Scenery::Gate { forward: !forward },
+        scenery => scenery,
+    }
+}
+
+// Tile turned a quarter clockwise (only ramps have a turned version)
+fn turned(scenery: Scenery) -> Scenery {
+    match scenery {
+        Scenery::ForwardWedge => Scenery::BackwardWedge,
+        Scenery::BackwardWedge => Scenery::ForwardWedge,
+        scenery => scenery,
+    }
+}
+
+/// Interactively build a level, saving it to `filename` (asked for if not given). An existing
+/// level file is opened for editing. Play-tests advance a tick every `tick_time`.
+pub fn run(filename: Option<String>, tick_time: time::Duration) -> Result<(), LevelError> {
+    // Prompt for filenames
+    let filename = match filename {
+        Some(filename) => filename,
+        None => prompt_for_filename()?,
+    };
+    let filename = filename.trim();
+    let mut editor = if Path::new(filename).exists() {
+        Editor::open(filename)?
+    } else {
+        Editor::blank()
+    };
+
+    // Initialise terminal
+    let mut stdout = stdout().into_raw_mode()?;
+    let mut stdin = termion::async_stdin().keys();
+    write!(stdout, "{}{}", clear::All, cursor::Hide)?;
+
+    // Determine initial terminal size
+    let mut term_size: (u16, u16) = (0, 0);
+    check_resize(&mut term_size);
+
+    // Editing loop
+    let mut complete = false;
+    let mut last_tick = time::Instant::now();
+    while !complete {
+        // Allow user to adjust level (input is asynchronous)
+        if let Some(Ok(key)) = stdin.next() {
+            match editor.press(key) {
+                Some(Request::Save) => {
+                    format::save_level(filename, editor.level())?;
+                    editor.message = format!("Saved to {}", filename);
+                }
+                Some(Request::Quit) => complete = true,
+                None => (),
+            }
+        }
+
+        // Crabs are advanced while play-testing
+        if last_tick.elapsed() >= tick_time {
+            editor.tick();
+            last_tick = time::Instant::now();
+        }
+
+        // Check if terminal has been resized
+        if check_resize(&mut term_size) {
+            // Clear before redraw
+            write!(stdout, "{}", clear::All)?;
+        }
+
+        draw(&mut stdout, &editor)?;
+    }
+
+    // Reset stdout
+    write!(
+        stdout,
+        "{}{}{}{}",
+        clear::All,
+        style::Reset,
+        cursor::Goto(1, 1),
+        cursor::Show
+    )?;
+
+    // Save user's level
+    Ok(format::save_level(filename, editor.level())?)
+}
+
+fn draw<W: Write>(stdout: &mut W, editor: &Editor) -> Result<(), std::io::Error> {
+    if let Some(simulation) = editor.trial() {
+        return draw_trial(stdout, simulation, editor.is_paused());
+    }
+    let level = editor.level();
+    let user = editor.cursor();
+
+    // Display the map, with the selected box highlighted and crabs on top
+    let selection = editor.selection();
+    for (y, x, ch) in level.map.clone() {
+        let selected = selection.is_some_and(|[[top, left], [bottom, right]]| {
+            (top..=bottom).contains(&y) && (left..=right).contains(&x)
+        });
+        if selected {
+            write!(
+                stdout,
+                "{}{}{}{}",
+                cursor::Goto(x as u16 + 1, y as u16 + 1),
+                style::Invert,
+                ch,
+                style::NoInvert
+            )?;
+        } else {
+            write!(stdout, "{}{}", cursor::Goto(x as u16 + 1, y as u16 + 1), ch)?;
+        }
+    }
+    for crab in level.entities.iter() {
+        let [y, x] = crab.position;
+        write!(
+            stdout,
+            "{}{}",
+            cursor::Goto(x as u16 + 1, y as u16 + 1),
+            crab.kind.to_char()
+        )?;
+    }
+    write!(
+        stdout,
+        "{}+",
+        cursor::Goto(user[1] as u16 + 1, user[0] as u16 + 1)
+    )?;
+
+    // Describe the cell under the cursor below the map, with a reminder of the keys
+    let row = level.map.dimensions[0] as u16;
+    let mode = match (editor.mode(), editor.tool()) {
+        (Mode::Steer, _) => "steer",
+        (Mode::Paint, None) => "paint",
+        (Mode::Paint, Some(Tool::Fill)) => "fill",
+        (Mode::Paint, Some(Tool::Outline)) => "outline",
+        (Mode::Paint, Some(Tool::Line)) => "line",
+        (Mode::Paint, Some(Tool::Flood)) => "flood fill",
+    };
+    write!(
+        stdout,
+        "{}{}[{}, {}] {}  ({})  {}",
+        cursor::Goto(1, row + 2),
+        clear::CurrentLine,
+        user[0],
+        user[1],
+        editor.inspect(),
+        mode,
+        editor.message()
+    )?;
+    let help = [
+        "Type a glyph to paint it (space clears), a crab glyph (. O Y * K) to add a crab,",
+        "v to steer, + and - to change a hatch's count, Delete to clear, u to undo, Ctrl-R to redo,",
+        "s to mark a corner, then b to fill, o to outline or l to draw a line up to the cursor,",
+        "f to flood fill, c to copy, p to paste (r to turn, m to mirror), Esc to cancel,",
+        "t to play-test, w to save and q to save and quit",
+    ];
+    for (line, text) in help.iter().enumerate() {
+        write!(
+            stdout,
+            "{}{}{}",
+            cursor::Goto(1, row + 3 + line as u16),
+            clear::CurrentLine,
+            text
+        )?;
+    }
+    stdout.flush()
+}
+
+// Show the play-test in place of the level being edited
+fn draw_trial<W: Write>(
+    stdout: &mut W,
+    simulation: &Simulation,
+    paused: bool,
+) -> Result<(), std::io::Error> {
+    for (y, x, ch) in simulation.map().clone() {
+        write!(stdout, "{}{}", cursor::Goto(x as u16 + 1, y as u16 + 1), ch)?;
+    }
+    for crab in simulation.entities().iter() {
+        let [y, x] = crab.position;
+        write!(
+            stdout,
+            "{}{}",
+            cursor::Goto(x as u16 + 1, y as u16 + 1),
+            crab.kind.to_char()
+        )?;
+    }
+
+    let row = simulation.map().dimensions[0] as u16;
+    let state = match simulation.outcome() {
+        Outcome::Playing if paused => "paused",
+        Outcome::Playing => "running",
+        Outcome::Won => "won",
+        Outcome::Lost => "lost",
+        Outcome::Quit => "stopped",
+    };
+    write!(
+        stdout,
+        "{}{}Play-testing ({}) tick {}  Saved: {}/{}  Remaining: {}  Lost: {}",
+        cursor::Goto(1, row + 2),
+        clear::CurrentLine,
+        state,
+        simulation.tick(),
+        simulation.rescued(),
+        simulation.quota(),
+        simulation.remaining(),
+        simulation.lost()
+    )?;
+    write!(
+        stdout,
+        "{}{}Space to pause, n to step a tick while paused, t or Esc to go back to editing",
+        cursor::Goto(1, row + 3),
+        clear::CurrentLine
+    )?;
+    // Clear the rest of the editor's reminder of the keys
+    for line in 4..8 {
+        write!(
+            stdout,
+            "{}{}",
+            cursor::Goto(1, row + line),
+            clear::CurrentLine
+        )?;
+    }
+    stdout.flush()
+}
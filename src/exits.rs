@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use termion::color;
+
+use crate::{Cause, Colour, Entities, Event, Map};
+
+/// Colour a crab is painted, deciding which exits it may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Team {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl Colour for Team {
+    fn to_fg_colour(&self) -> String {
+        match self {
+            Self::Red => format!("{}", color::Fg(color::LightRed)),
+            Self::Green => format!("{}", color::Fg(color::LightGreen)),
+            Self::Blue => format!("{}", color::Fg(color::LightBlue)),
+            Self::Yellow => format!("{}", color::Fg(color::LightYellow)),
+        }
+    }
+}
+
+/// A named safety tile, which may only take so many crabs, or only crabs of one team.
+/// Safety tiles without an exit take any number of any crab.
+///
+/// A crab that reaches a safety tile always leaves the map, so one arriving at an exit that's
+/// full (or for another team) is lost rather than turned away. Sorting puzzles are then about
+/// steering each crab to an exit that will have it, and overfilling an exit costs crabs in the
+/// same way as walking into a hazard.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Exit {
+    pub name: String,
+    /// Position [y, x] of the safety tile
+    pub position: [usize; 2],
+    /// Most crabs the exit takes (None if unlimited)
+    pub capacity: Option<usize>,
+    /// Team of crab the exit takes (None if any)
+    pub team: Option<Team>,
+    // IDs of the crabs that have left through the exit, in the order they did so
+    used: Vec<usize>,
+}
+
+impl Exit {
+    pub fn new(
+        name: &str,
+        position: [usize; 2],
+        capacity: Option<usize>,
+        team: Option<Team>,
+    ) -> Self {
+        Exit {
+            name: name.to_string(),
+            position,
+            capacity,
+            team,
+            used: Vec::new(),
+        }
+    }
+
+    /// IDs of the crabs that have left through the exit
+    pub fn used(&self) -> &[usize] {
+        &self.used
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|capacity| self.used.len() >= capacity)
+    }
+
+    /// Whether a crab of this team counts when it gets here
+    pub fn admits(&self, team: Option<Team>) -> bool {
+        self.team.map_or(true, |colour| team == Some(colour))
+    }
+}
+
+impl Map {
+    /// Exit at this cell, if it has been named
+    pub fn exit(&self, cell: [usize; 2]) -> Option<&Exit> {
+        self.exits.iter().find(|exit| exit.position == cell)
+    }
+
+    /// Let crabs that reached safety out through the exits they got to, lowest ID first so the
+    /// same crabs fill an exit whatever order they are in. Crabs on the wrong team, or reaching
+    /// an exit that's full, don't count and are lost.
+    pub(crate) fn depart(
+        &mut self,
+        mut arrivals: Vec<(usize, [usize; 2], Option<Team>)>,
+    ) -> Vec<Event> {
+        arrivals.sort_unstable_by_key(|&(crab, _, _)| crab);
+        arrivals
+            .into_iter()
+            .map(|(crab, cell, team)| {
+                match self.exits.iter_mut().find(|exit| exit.position == cell) {
+                    None => Event::Rescued { crab, exit: None },
+                    Some(exit) if !exit.admits(team) => Event::Died {
+                        crab,
+                        cause: Cause::WrongExit(exit.name.clone()),
+                    },
+                    Some(exit) if exit.is_full() => Event::Died {
+                        crab,
+                        cause: Cause::ExitFull(exit.name.clone()),
+                    },
+                    Some(exit) => {
+                        exit.used.push(crab);
+                        Event::Rescued {
+                            crab,
+                            exit: Some(exit.name.clone()),
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl Entities {
+    /// Put crabs on teams, in the same order as the crabs were given
+    pub fn with_teams(mut self, teams: Vec<Option<Team>>) -> Self {
+        for (crab, team) in self.collection.iter_mut().zip(teams) {
+            crab.team = team;
+        }
+        self
+    }
+}
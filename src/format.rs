@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+use ron::de::from_str;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::validate::{validate_level, Diagnostic};
+use crate::{
+    Boundaries, Continuous, Entities, Exit, Inventory, Kind, Level, Link, Map, Scenery, Skill,
+    Skills, Spawner, Team, Teleporter, FALL_LIMIT, SCALE,
+};
+
+/// Current version of the level file format
+pub const VERSION: u32 = 2;
+
+/// Level file (version 2)
+///
+/// ```text
+/// (
+///     version: 2,
+///     name: "Tiny",
+///     author: "drvog",
+///     par: Some(1),
+///     inventory: Some({ '/': 1 }),
+///     skills: Some({ Blocker: 1, Builder: 2 }),
+///     physics: Some((gravity: 0.25, friction: 0.1, restitution: 0.5)),
+///     boundaries: Some((bottom: Kill, left: Wall, right: Wall)),
+///     crabs: [(pos: (1, 2), vel: (1, -1)), (pos: (1, 0), vel: (0, 1), kind: King)],
+///     spawners: [(pos: (0, 1), count: 5, interval: 3, vel: (0, 1), team: Some(Red))],
+///     exits: [(name: "A", pos: (1, 2), capacity: Some(3), team: Some(Red))],
+///     links: [(plate: (2, 0), doors: [(1, 1)])],
+///     teleporters: [(id: 1, pads: ((0, 0), (2, 1)))],
+///     layout: "T X\n D \n_T#",
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelFile {
+    pub version: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    /// Number of placements an expert needs to complete the level
+    #[serde(default)]
+    pub par: Option<usize>,
+    /// Number of crabs that must reach safety (defaults to all of them)
+    #[serde(default)]
+    pub rescue: Option<usize>,
+    #[serde(default)]
+    pub fall_limit: Option<usize>,
+    /// Placeable scenery, keyed by glyph (defaults to unlimited)
+    #[serde(default)]
+    pub inventory: Option<BTreeMap<char, usize>>,
+    /// Jobs the player can give crabs (defaults to none)
+    #[serde(default)]
+    pub skills: Option<BTreeMap<Skill, usize>>,
+    /// Continuous physics settings (defaults to moving crabs a cell at a time)
+    #[serde(default)]
+    pub physics: Option<PhysicsSpec>,
+    /// What each edge of the map does to crabs (any left out wrap around, as do all by default)
+    #[serde(default)]
+    pub boundaries: Option<Boundaries>,
+    pub crabs: Vec<CrabSpec>,
+    /// Hatches that release more crabs during the level
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spawners: Vec<SpawnerSpec>,
+    /// Named safety tiles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exits: Vec<ExitSpec>,
+    /// Pressure plates and the doors each one switches
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<LinkSpec>,
+    /// Pairs of teleporter pads
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teleporters: Vec<TeleporterSpec>,
+    pub layout: String,
+}
+
+/// A hatch (`H` in the layout) and the crabs it releases
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnerSpec {
+    /// Position (y, x) of the hatch
+    pub pos: (usize, usize),
+    /// Number of crabs released
+    pub count: usize,
+    /// Ticks between crabs
+    pub interval: usize,
+    /// Velocity (vy, vx) of each crab
+    pub vel: (isize, isize),
+    #[serde(default, skip_serializing_if = "Kind::is_common")]
+    pub kind: Kind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+}
+
+/// A named safety tile (`X` in the layout)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExitSpec {
+    pub name: String,
+    /// Position (y, x) of the safety tile
+    pub pos: (usize, usize),
+    /// Most crabs the exit takes (defaults to unlimited)
+    #[serde(default)]
+    pub capacity: Option<usize>,
+    /// Team of crab the exit takes (defaults to any)
+    #[serde(default)]
+    pub team: Option<Team>,
+}
+
+/// A pressure plate (`_` in the layout) and the doors (`D` or `d`) it switches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkSpec {
+    /// Position (y, x) of the plate
+    pub plate: (usize, usize),
+    /// Positions (y, x) of the doors
+    pub doors: Vec<(usize, usize)>,
+}
+
+/// A pair of teleporter pads (`T` in the layout)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeleporterSpec {
+    pub id: usize,
+    /// Positions (y, x) of the pads
+    pub pads: ((usize, usize), (usize, usize)),
+}
+
+/// Continuous physics, in cells rather than fixed point so levels are easy to write.
+/// Any setting left out takes its default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsSpec {
+    /// Speed gained falling each tick, in cells per tick
+    #[serde(default = "default_gravity")]
+    pub gravity: f64,
+    /// Fraction of horizontal speed lost each tick on the ground, from 0 to 1
+    #[serde(default)]
+    pub friction: f64,
+    /// Fraction of vertical speed kept bouncing off a floor or ceiling, from 0 to 1
+    #[serde(default)]
+    pub restitution: f64,
+}
+
+fn default_gravity() -> f64 {
+    from_fixed(Continuous::default().gravity)
+}
+
+fn to_fixed(value: f64) -> isize {
+    (value * SCALE as f64).round() as isize
+}
+
+fn from_fixed(value: isize) -> f64 {
+    value as f64 / SCALE as f64
+}
+
+impl PhysicsSpec {
+    pub fn from_continuous(rules: Continuous) -> Self {
+        PhysicsSpec {
+            gravity: from_fixed(rules.gravity),
+            friction: from_fixed(rules.friction),
+            restitution: from_fixed(rules.restitution),
+        }
+    }
+
+    pub fn to_continuous(&self) -> Continuous {
+        Continuous {
+            gravity: to_fixed(self.gravity),
+            friction: to_fixed(self.friction),
+            restitution: to_fixed(self.restitution),
+        }
+    }
+}
+
+/// Starting state of a single crab
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrabSpec {
+    /// Optional label, kept from the ID used in legacy files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u16>,
+    /// Position (y, x)
+    pub pos: (usize, usize),
+    /// Velocity (vy, vx)
+    pub vel: (isize, isize),
+    /// Species of crab (common by default)
+    #[serde(default, skip_serializing_if = "Kind::is_common")]
+    pub kind: Kind,
+    /// Team the crab is on, for exits that only take one team (none by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+}
+
+/// Original level file, with crabs spread across maps keyed by crab ID
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyLevel {
+    x: HashMap<u16, u16>,
+    y: HashMap<u16, u16>,
+    vx: HashMap<i16, i16>,
+    vy: HashMap<i16, i16>,
+    layout: String,
+    #[serde(default)]
+    rescue: Option<usize>,
+    #[serde(default)]
+    fall_limit: Option<usize>,
+    #[serde(default)]
+    inventory: Option<BTreeMap<char, usize>>,
+}
+
+// Just enough of a level file to decide how to decode the rest
+#[derive(Debug, Deserialize)]
+struct Probe {
+    #[serde(default, deserialize_with = "written")]
+    version: Option<u32>,
+    // Legacy files have no version field, but always have an x map
+    #[serde(default, deserialize_with = "present")]
+    x: bool,
+}
+
+// Fields that may be left out, written without Some(...)
+fn written<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    u32::deserialize(deserializer).map(Some)
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    IgnoredAny::deserialize(deserializer).map(|_| true)
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Parse(ron::de::Error),
+    /// File claims a version of the format this build doesn't understand
+    Version(u32),
+    /// File has no version field and isn't a legacy level either
+    MissingVersion,
+    /// Legacy file has a crab ID missing from one of its maps
+    MissingCrab(u16),
+    /// File decodes, but the level in it can't be played
+    Invalid(Vec<Diagnostic>),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "unable to read level file: {}", err),
+            LevelError::Parse(err) => write!(f, "unable to parse level file: {}", err),
+            LevelError::Version(version) => write!(
+                f,
+                "level file version {} is not supported (expected {} or earlier)",
+                version, VERSION
+            ),
+            LevelError::MissingVersion => {
+                write!(f, "level file needs a version (currently {})", VERSION)
+            }
+            LevelError::MissingCrab(id) => {
+                write!(f, "crab {} needs an entry in each of x, y, vx and vy", id)
+            }
+            LevelError::Invalid(diagnostics) => {
+                write!(f, "level has {} problem(s)", diagnostics.len())?;
+                for diagnostic in diagnostics.iter() {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LevelError {
+    fn from(err: io::Error) -> Self {
+        LevelError::Io(err)
+    }
+}
+
+impl From<ron::de::Error> for LevelError {
+    fn from(err: ron::de::Error) -> Self {
+        LevelError::Parse(err)
+    }
+}
+
+impl LevelFile {
+    /// Decode a level file in any supported version
+    pub fn parse(source: &str) -> Result<Self, LevelError> {
+        let probe: Probe = from_str(source)?;
+        match probe.version {
+            None if probe.x => Ok(LevelFile::migrate(from_str(source)?)?),
+            None => Err(LevelError::MissingVersion),
+            Some(1) => Ok(LevelFile::migrate(from_str(source)?)?),
+            Some(VERSION) => Ok(from_str(source)?),
+            Some(version) => Err(LevelError::Version(version)),
+        }
+    }
+
+    /// Convert a legacy level, pairing each crab's position and velocity by ID
+    pub fn migrate(legacy: LegacyLevel) -> Result<Self, LevelError> {
+        let mut ids: Vec<u16> = legacy.x.keys().copied().collect();
+        ids.sort_unstable();
+
+        let mut crabs: Vec<CrabSpec> = Vec::new();
+        for id in ids {
+            let signed = id as i16;
+            match (
+                legacy.x.get(&id),
+                legacy.y.get(&id),
+                legacy.vx.get(&signed),
+                legacy.vy.get(&signed),
+            ) {
+                (Some(&x), Some(&y), Some(&vx), Some(&vy)) => crabs.push(CrabSpec {
+                    id: Some(id),
+                    pos: (y as usize, x as usize),
+                    vel: (vy as isize, vx as isize),
+                    kind: Kind::Common,
+                    team: None,
+                }),
+                _ => return Err(LevelError::MissingCrab(id)),
+            }
+        }
+
+        Ok(LevelFile {
+            version: VERSION,
+            name: String::new(),
+            author: String::new(),
+            par: None,
+            rescue: legacy.rescue,
+            fall_limit: legacy.fall_limit,
+            inventory: legacy.inventory,
+            skills: None,
+            physics: None,
+            boundaries: None,
+            crabs,
+            spawners: Vec::new(),
+            exits: Vec::new(),
+            links: Vec::new(),
+            teleporters: Vec::new(),
+            layout: legacy.layout,
+        })
+    }
+
+    /// Describe an existing level
+    pub fn from_level(level: &Level) -> Self {
+        let inventory = if level.map.inventory.is_limited() {
+            Some(
+                level
+                    .map
+                    .inventory
+                    .counts()
+                    .into_iter()
+                    .map(|(scenery, count)| (scenery.to_char(), count))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        // Crabs are stamped onto the map when a level is played, so leave them out of the layout
+        let mut map = level.map.clone();
+        map.decrab();
+
+        LevelFile {
+            version: VERSION,
+            name: level.name.clone(),
+            author: level.author.clone(),
+            par: level.par,
+            rescue: if level.rescue == level.entities.len() + level.entities.pending() {
+                None
+            } else {
+                Some(level.rescue)
+            },
+            fall_limit: if map.physics.fall_limit == FALL_LIMIT {
+                None
+            } else {
+                Some(map.physics.fall_limit)
+            },
+            inventory,
+            skills: if level.skills.is_empty() {
+                None
+            } else {
+                Some(level.skills.counts().into_iter().collect())
+            },
+            physics: map.physics.continuous.map(PhysicsSpec::from_continuous),
+            boundaries: if map.boundaries == Boundaries::default() {
+                None
+            } else {
+                Some(map.boundaries)
+            },
+            crabs: level
+                .entities
+                .iter()
+                .map(|crab| {
+                    let [y, x] = crab.position();
+                    let [vy, vx] = crab.velocity();
+                    CrabSpec {
+                        id: None,
+                        pos: (y, x),
+                        vel: (vy, vx),
+                        kind: crab.kind(),
+                        team: crab.team(),
+                    }
+                })
+                .collect(),
+            spawners: level
+                .entities
+                .spawners()
+                .iter()
+                .map(|spawner| SpawnerSpec {
+                    pos: (spawner.position[0], spawner.position[1]),
+                    count: spawner.count,
+                    interval: spawner.interval,
+                    vel: (spawner.velocity[0], spawner.velocity[1]),
+                    kind: spawner.kind,
+                    team: spawner.team,
+                })
+                .collect(),
+            exits: map
+                .exits
+                .iter()
+                .map(|exit| ExitSpec {
+                    name: exit.name.clone(),
+                    pos: (exit.position[0], exit.position[1]),
+                    capacity: exit.capacity,
+                    team: exit.team,
+                })
+                .collect(),
+            links: map
+                .links
+                .iter()
+                .map(|link| LinkSpec {
+                    plate: (link.plate[0], link.plate[1]),
+                    doors: link.doors.iter().map(|door| (door[0], door[1])).collect(),
+                })
+                .collect(),
+            teleporters: map
+                .teleporters
+                .iter()
+                .map(|teleporter| {
+                    let [first, second] = teleporter.pads;
+                    TeleporterSpec {
+                        id: teleporter.id,
+                        pads: ((first[0], first[1]), (second[0], second[1])),
+                    }
+                })
+                .collect(),
+            layout: map.to_string(),
+        }
+    }
+
+    pub fn into_level(self) -> Level {
+        let entities = Entities::with_kinds(
+            self.crabs
+                .iter()
+                .map(|crab| [crab.pos.0, crab.pos.1])
+                .collect(),
+            self.crabs
+                .iter()
+                .map(|crab| [crab.vel.0, crab.vel.1])
+                .collect(),
+            self.crabs.iter().map(|crab| crab.kind).collect(),
+        )
+        .with_teams(self.crabs.iter().map(|crab| crab.team).collect())
+        .with_spawners(
+            self.spawners
+                .iter()
+                .map(|spawner| {
+                    let mut hatch = Spawner::new(
+                        [spawner.pos.0, spawner.pos.1],
+                        spawner.count,
+                        spawner.interval,
+                        [spawner.vel.0, spawner.vel.1],
+                        spawner.kind,
+                    );
+                    hatch.team = spawner.team;
+                    hatch
+                })
+                .collect(),
+        );
+
+        let mut map = Map::new(&self.layout);
+        if let Some(fall_limit) = self.fall_limit {
+            map.physics.fall_limit = fall_limit;
+        }
+        map.physics.continuous = self.physics.as_ref().map(PhysicsSpec::to_continuous);
+        map.boundaries = self.boundaries.unwrap_or_default();
+        map.exits = self
+            .exits
+            .iter()
+            .map(|exit| {
+                Exit::new(
+                    &exit.name,
+                    [exit.pos.0, exit.pos.1],
+                    exit.capacity,
+                    exit.team,
+                )
+            })
+            .collect();
+        map.links = self
+            .links
+            .iter()
+            .map(|link| Link {
+                plate: [link.plate.0, link.plate.1],
+                doors: link.doors.iter().map(|door| [door.0, door.1]).collect(),
+            })
+            .collect();
+        map.teleporters = self
+            .teleporters
+            .iter()
+            .map(|teleporter| {
+                let (first, second) = teleporter.pads;
+                Teleporter {
+                    id: teleporter.id,
+                    pads: [[first.0, first.1], [second.0, second.1]],
+                }
+            })
+            .collect();
+        if let Some(budget) = self.inventory {
+            map.inventory = Inventory::limited(
+                budget
+                    .into_iter()
+                    .filter_map(|(ch, count)| {
+                        Scenery::from_char(ch)
+                            .filter(|scenery| Scenery::PLACEABLE.contains(scenery))
+                            .map(|scenery| (scenery, count))
+                    })
+                    .collect(),
+            );
+        }
+
+        let mut level = Level::new(entities, map);
+        if let Some(rescue) = self.rescue {
+            level.rescue = rescue;
+        }
+        if let Some(skills) = self.skills {
+            level.skills = Skills::new(skills.into_iter().collect());
+        }
+        level.name = self.name;
+        level.author = self.author;
+        level.par = self.par;
+        level
+    }
+
+    pub fn to_ron(&self) -> String {
+        // Serialising plain data can't fail
+        to_string_pretty(self, PrettyConfig::default()).unwrap_or_default()
+    }
+}
+
+/// Read a level from a RON file (either format version), rejecting it if it has any problems
+pub fn load_level(path: &str) -> Result<Level, LevelError> {
+    let source = fs::read_to_string(path)?;
+    let diagnostics = validate_level(&source);
+    if diagnostics.is_empty() {
+        Ok(LevelFile::parse(&source)?.into_level())
+    } else {
+        Err(LevelError::Invalid(diagnostics))
+    }
+}
+
+/// Write a level to a RON file in the current format
+pub fn save_level(path: &str, level: &Level) -> Result<(), io::Error> {
+    let mut file = File::create(path.trim())?;
+    writeln!(file, "// {}", level_title(level))?;
+    writeln!(file, "{}", LevelFile::from_level(level).to_ron())
+}
+
+fn level_title(level: &Level) -> &str {
+    if level.name.is_empty() {
+        "Custom level"
+    } else {
+        &level.name
+    }
+}
@@ -1,8 +1,8 @@
-use crate::{Entities, Map};
+use crate::{Entities, Level, Map};
 
 /// Load in the default levels
-pub fn default_levels() -> Vec<(Entities, Map)> {
-    let mut default: Vec<(Entities, Map)> = Vec::new();
+pub fn default_levels() -> Vec<Level> {
+    let mut default: Vec<Level> = Vec::new();
     let levels = [level_1, level_2, level_3];
     for level in levels.iter() {
         default.push(level())
@@ -11,42 +11,46 @@ pub fn default_levels() -> Vec<(Entities, Map)> {
 }
 
 /// Default level 1
-pub fn level_1() -> (Entities, Map) {
+pub fn level_1() -> Level {
     let layout: &str = "#                                                                               \n#                        #                                                      \n##########################                                                      \n                         #                                                      \n                         #                                                      \n                         #                                                      \n                       X #                                                      \n               ###########                                                      \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n              #                                                          #      \n##############                                                           #######\n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                        #                                                      #\n                         ######################################################";
     let positions: Vec<[usize; 2]> = vec![[1, 1], [1, 12], [1, 24]];
     let velocities: Vec<[isize; 2]> = vec![[0, 1], [0, -1], [0, -1]];
     let crabs = Entities::new(positions, velocities);
     let map = Map::new(layout);
 
-    (crabs, map)
+    let mut level = Level::new(crabs, map);
+    level.name = String::from("Level 1");
+    level
 }
 
 /// Default level 2
-pub fn level_2() -> (Entities, Map) {
+pub fn level_2() -> Level {
     let layout: &str = "                                                                                \n                                                                                \n#                         #                                                     \n########################### ####################################################\n                              #                                                 \n                            /                                                   \n###################  ###############    ########################################\n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n               #         #                                                      \n               #         #                                                      \n               #         #                                                      \n               #  # #    #                                                      \n               #### #################                                           \n               #                    #                                           \n               #                    #                                           \n               #        X           #                                           \n################################################################################\n                                                                                \n";
     let positions: Vec<[usize; 2]> = vec![[2, 1], [2, 25]];
     let velocities: Vec<[isize; 2]> = vec![[0, 1], [0, -1]];
     let crabs = Entities::new(positions, velocities);
     let map = Map::new(layout);
 
-    (crabs, map)
+    let mut level = Level::new(crabs, map);
+    level.name = String::from("Level 2");
+    level
 }
 
-/// Default level 2
-pub fn level_3() -> (Entities, Map) {
+/// Default level 3
+pub fn level_3() -> Level {
     let layout: &str = "############                                                                    \n#            /### #      ##\\                                                    \n#          ##      ######  #######                                              \n############                         ##                                         \n                                  ###                                           \n                                                                                \n                                                                                \n                                                                                \n                                              #############                     \n                                                                                \n                                                                                \n                                          ##########@##########                 \n             ############\\                                                      \n                          \\                                                     \n                           \\                                                    \n                            \\                                                   \n             #####           \\                                                  \n            #  X  #           \\                                                 \n           #       #           \\                                                \n          #         ####        \\                                               \n           #                     \\###                                           \n            #     ######\\           #                                           \n             #####       #### ##    #                                           \n                                #####                                          ";
     let positions: Vec<[usize; 2]> = vec![[2, 1], [2, 10]];
     let velocities: Vec<[isize; 2]> = vec![[0, 1], [0, -1]];
     let crabs = Entities::new(positions, velocities);
     let map = Map::new(layout);
 
-    (crabs, map)
+    let mut level = Level::new(crabs, map);
+    level.name = String::from("Level 3");
+    level
 }
 
 /// Blank map
 pub fn blank_map() -> Map {
     let layout: &str = "                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n                                                                                \n";
-    let map = Map::new(layout);
-
-    map
+    Map::new(layout)
 }
@@ -1,266 +1,608 @@
+pub mod cli;
+pub mod editor;
+pub mod exits;
+pub mod format;
 pub mod levels;
-
-use std::cmp;
-use std::collections::HashMap;
-use std::env::Args;
-use std::fs::File;
-use std::io::{stdin, Write};
-use std::{process, time};
-
-use ron::de::from_reader;
-use serde::Deserialize;
+pub mod mechanisms;
+pub mod pack;
+pub mod progress;
+pub mod replay;
+pub mod simulation;
+pub mod skills;
+pub mod solve;
+pub mod spawner;
+pub mod teleport;
+pub mod validate;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::stdin;
+use std::time;
+
+use serde::{Deserialize, Serialize};
 use termion::color;
 use termion::event::Key;
 
+pub use exits::{Exit, Team};
+pub use mechanisms::Link;
+pub use pack::Pack;
+pub use simulation::{Edit, Event, Outcome, Rng, Simulation};
+pub use skills::{Skill, Skills};
+pub use spawner::Spawner;
+pub use teleport::Teleporter;
+
+use skills::Job;
+
 // Target tick time which will be the minimum period between iterations of the game loop.
 pub const TICK_TIME: time::Duration = time::Duration::from_millis(100);
 
 // Terminal velocity
 pub const VMAX: isize = 10;
 
-pub fn process_args(mut args: Args, rate: &mut time::Duration) -> Vec<(Entities, Map)> {
-    // Skip executable name
-    args.next();
-
-    match args.next() {
-        Some(arg) => {
-            match arg.as_str() {
-                // Print help
-                "--help" => {
-                    println!("\ncrabs --help");
-                    println!("------------");
-                    println!("Guide the crabs to safety:");
-                    println!("\t. crab");
-                    println!("\tX safety");
-                    println!("Use the arrow keys to move the cursor:");
-                    println!("\t+ cursor");
-                    println!("Insert scenery by typing the appropriate key:");
-                    println!("\t# block");
-                    println!("\t/ forward ramp");
-                    println!("\t\\ backward ramp");
-                    println!("\t@ trampoline");
-                    println!("Each level, type r to restart and q to quit.\n");
-                    println!("Use a custom map saved in RON format:");
-                    println!("\t$ crabs custom_level.ron");
-                    println!("To adjust the crab speed:");
-                    println!("\t$ crabs --tick-time N");
-                    println!("where larger N makes the crabs slower! Default is 100ms\n");
-                    process::exit(0);
-                }
+// Default number of cells a crab can fall at terminal velocity and survive the landing
+pub const FALL_LIMIT: usize = 2 * VMAX as usize;
 
-                // User adjusted rate
-                "--tick-time" => match args.next() {
-                    Some(new_rate) => {
-                        *rate = match new_rate.parse::<usize>() {
-                            Ok(r) => {
-                                eprintln!("Using adjusted rate: {}", new_rate);
-                                time::Duration::from_millis(r as u64)
-                            }
-                            Err(_) => {
-                                eprintln!("{} not a valid rate!", new_rate);
-                                TICK_TIME
-                            }
-                        };
-                        levels::default_levels()
-                    }
-                    None => {
-                        eprintln!("No rate provided...");
-                        levels::default_levels()
-                    }
-                },
+// Fixed point scale for continuous physics: positions and speeds are in 1/SCALE of a cell
+pub const SCALE: isize = 256;
 
-                // Load custom level
-                path => load_level(&path),
-            }
-        }
-        None => {
-            // Load default level
-            levels::default_levels()
-        }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-pub struct LoadedLevel {
-    x: HashMap<u16, u16>,
-    y: HashMap<u16, u16>,
-    vx: HashMap<i16, i16>,
-    vy: HashMap<i16, i16>,
-    layout: String,
+/// A playable level: the map, its crabs and what counts as success
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub name: String,
+    pub author: String,
+    /// Number of placements an expert needs to complete the level
+    pub par: Option<usize>,
+    pub entities: Entities,
+    pub map: Map,
+    /// Number of crabs that must reach safety to complete the level
+    pub rescue: usize,
+    /// Jobs the player can give crabs
+    pub skills: Skills,
 }
 
-fn load_level(path: &str) -> Vec<(Entities, Map)> {
-    match File::open(path) {
-        Ok(file) => {
-            // Decode RON format of configuration file
-            let loaded: LoadedLevel = match from_reader(file) {
-                Ok(x) => x,
-                Err(err) => {
-                    eprintln!("Unable to read custom level: {:?}", err);
-                    return levels::default_levels();
-                }
-            };
-            let entities = Entities::new(
-                convert_to_vec(loaded.y, loaded.x),
-                convert_to_vec(loaded.vy, loaded.vx),
-            );
-            let map = Map::new(&loaded.layout);
-            vec![(entities, map)]
-        }
-        Err(err) => {
-            eprintln!("Unable to read custom level file: {:?}", err.kind());
-            levels::default_levels()
+impl Level {
+    /// Create an untitled level where every crab must be rescued
+    pub fn new(entities: Entities, map: Map) -> Self {
+        let rescue = entities.len() + entities.pending();
+        Level {
+            name: String::new(),
+            author: String::new(),
+            par: None,
+            entities,
+            map,
+            rescue,
+            skills: Skills::none(),
         }
     }
 }
 
-fn convert_to_vec<T: Copy, U: From<T>>(x: HashMap<T, T>, y: HashMap<T, T>) -> Vec<[U; 2]> {
-    let mut store: Vec<[U; 2]> = Vec::new();
-    for ((_, xi), (_, yi)) in x.iter().zip(y.iter()) {
-        store.push([U::from(*xi), U::from(*yi)]);
-    }
-    eprintln!("Loaded {} crabs.", store.len());
-    store
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Entities {
     collection: Vec<Crab>,
+    spawners: Vec<Spawner>,
+    // Number of crabs there have been, so released crabs get the next ID
+    created: usize,
+    // Crabs that must not be lost, including any that have already left
+    kings: Vec<usize>,
 }
 
 impl Entities {
     pub fn new(positions: Vec<[usize; 2]>, velocities: Vec<[isize; 2]>) -> Self {
+        Entities::with_kinds(positions, velocities, Vec::new())
+    }
+
+    /// Like `new`, but with the kind of each crab (any left out are common crabs)
+    pub fn with_kinds(
+        positions: Vec<[usize; 2]>,
+        velocities: Vec<[isize; 2]>,
+        kinds: Vec<Kind>,
+    ) -> Self {
         let mut collection: Vec<Crab> = Vec::new();
-        for (position, velocity) in positions.iter().zip(velocities.iter()) {
-            collection.push(Crab::new(*position, *velocity));
+        for (id, (position, velocity)) in positions.iter().zip(velocities.iter()).enumerate() {
+            let kind = kinds.get(id).copied().unwrap_or_default();
+            collection.push(Crab::new(id, *position, *velocity, kind));
+        }
+        Entities {
+            created: collection.len(),
+            kings: collection
+                .iter()
+                .filter(|crab| crab.kind == Kind::King)
+                .map(|crab| crab.id)
+                .collect(),
+            collection,
+            spawners: Vec::new(),
         }
-        Entities { collection }
     }
 
-    pub fn evolve(&mut self, map: &mut Map, complete: &mut bool) {
-        // Add positions of crab to map
-        map.instantaneous(&self);
+    pub fn evolve(&mut self, map: &mut Map) -> Vec<Event> {
+        // Let out any crabs due from hatches
+        let mut events: Vec<Event> = self
+            .release(map)
+            .into_iter()
+            .map(|crab| Event::Released { crab })
+            .collect();
+
+        // Add positions of crab to map, so every crab sees the others where they started the tick
+        events.extend(
+            map.instantaneous(self)
+                .into_iter()
+                .map(|crab| Event::Embedded { crab }),
+        );
+
+        // Crabs get on with their jobs, then advance on their own, then any that got in each
+        // other's way are settled
+        for crab in self.collection.iter_mut() {
+            crab.work(map);
+        }
+        let before = self.collection.clone();
+        let fates: Vec<Fate> = self
+            .collection
+            .iter_mut()
+            .map(|entity| entity.advance(map))
+            .collect();
+        self.collide(&before, &fates);
+        let mut rework: Vec<([usize; 2], Scenery, Scenery)> = self
+            .collection
+            .iter()
+            .flat_map(|crab| crab.rework.iter().copied())
+            .collect();
 
         let mut remove: Vec<usize> = Vec::new();
-        for (index, entity) in self.collection.iter_mut().enumerate() {
-            // Advance each crab one after the other
-            if entity.advance(map) {
-                remove.push(index);
+        let mut arrivals = Vec::new();
+        for (index, (entity, fate)) in self.collection.iter().zip(fates).enumerate() {
+            match fate {
+                Fate::Alive => (),
+                Fate::Rescued(cell) => {
+                    remove.push(index);
+                    arrivals.push((entity.id, cell, entity.team));
+                }
+                Fate::Died(cause) => {
+                    remove.push(index);
+                    events.push(Event::Died {
+                        crab: entity.id,
+                        cause,
+                    });
+                }
             }
         }
 
-        // Remove any crabs that made it to safety
-        for index in remove.iter() {
+        events.extend(map.depart(arrivals));
+
+        // Remove any crabs that made it to safety or died (back to front so indices stay valid)
+        for index in remove.iter().rev() {
             self.collection.remove(*index);
         }
 
-        // When all crabs are safe, game is complete
-        if self.collection.len() == 0 {
-            *complete = true;
+        // Show crabs where they ended up
+        map.decrab();
+        map.instantaneous(self);
+
+        // Change the scenery now nothing else can run into it, taking it away before adding any
+        // (so whatever order the crabs are in, it ends up the same)
+        rework.sort_by_key(|&(cell, from, to)| {
+            (to != Scenery::Empty, cell, from.to_char(), to.to_char())
+        });
+        for (cell, from, to) in rework {
+            map.rework(cell, from, to);
+        }
+
+        // Doors open or shut for the next tick, depending on where the crabs ended up
+        map.switch();
+
+        events
+    }
+
+    /// Settle crabs that got in each other's way, the same whatever order they are in.
+    ///
+    /// Crabs run into each other where they were at the start of the tick, so a crab that
+    /// catches up with (or meets) another stops behind it, and the two swap horizontal speeds
+    /// if they were closing. Crabs whose paths cross are different: whichever got to the
+    /// shared cell first carries on and the other is held back where it started. Crabs
+    /// meeting head on, or arriving together, are both held back (swapping speeds head on).
+    fn collide(&mut self, before: &[Crab], fates: &[Fate]) {
+        let alive: Vec<usize> = (0..fates.len())
+            .filter(|&index| fates[index] == Fate::Alive)
+            .collect();
+        let mut swaps: Vec<(usize, usize)> = Vec::new();
+        let mut turns: Vec<usize> = Vec::new();
+
+        // Crabs that ran into where another crab was
+        for &index in alive.iter() {
+            let crab = &self.collection[index];
+            for cell in crab.bumped.iter() {
+                if let Some(&other) = alive.iter().find(|&&other| before[other].position == *cell) {
+                    if self.collection[other].is_blocking() {
+                        turns.push(index);
+                        continue;
+                    }
+                    let pair = (index.min(other), index.max(other));
+                    let direction = crab.speed[1].signum();
+                    let closing =
+                        crab.speed[1] * direction > self.collection[other].speed[1] * direction;
+                    if closing && !swaps.contains(&pair) {
+                        swaps.push(pair);
+                    }
+                }
+            }
+        }
+
+        // Crabs whose paths cross, repeated as holding crabs back clears the way for others
+        let mut held = vec![false; fates.len()];
+        loop {
+            let mut losers: Vec<usize> = Vec::new();
+            for (n, &first) in alive.iter().enumerate() {
+                for &second in alive[n + 1..].iter() {
+                    if held[first] || held[second] {
+                        continue;
+                    }
+                    let (a, b) = (&self.collection[first], &self.collection[second]);
+                    let (arrival_a, arrival_b) = match (a.arrival(b), b.arrival(a)) {
+                        (Some(arrival_a), Some(arrival_b)) => (arrival_a, arrival_b),
+                        _ => continue,
+                    };
+
+                    let head_on = before[first].position[0] == before[second].position[0]
+                        && before[first].speed[1] * before[second].speed[1] < 0;
+                    if head_on {
+                        losers.extend([first, second]);
+                        if !swaps.contains(&(first, second)) {
+                            swaps.push((first, second));
+                        }
+                    } else if arrival_a < arrival_b {
+                        losers.push(second);
+                    } else if arrival_b < arrival_a {
+                        losers.push(first);
+                    } else {
+                        losers.extend([first, second]);
+                    }
+                }
+            }
+            if losers.is_empty() {
+                break;
+            }
+            for index in losers {
+                held[index] = true;
+                let crab = &mut self.collection[index];
+                crab.position = before[index].position;
+                crab.offset = before[index].offset;
+                crab.falling = before[index].falling;
+                crab.cooldown = before[index].cooldown;
+                crab.path.clear();
+                crab.rework = before[index].rework.clone();
+            }
+        }
+
+        // A crab can be in more than one exchange, so make them in order of where the crabs
+        // started rather than where they happen to be in the list
+        for pair in swaps.iter_mut() {
+            if before[pair.1].position < before[pair.0].position {
+                *pair = (pair.1, pair.0);
+            }
+        }
+        swaps.sort_by_key(|&(first, second)| (before[first].position, before[second].position));
+        for (first, second) in swaps {
+            let (speed, other) = (
+                self.collection[first].speed[1],
+                self.collection[second].speed[1],
+            );
+            self.collection[first].pace(other);
+            self.collection[second].pace(speed);
+        }
+
+        // Blockers turn crabs back
+        turns.sort_unstable();
+        turns.dedup();
+        for index in turns {
+            let speed = self.collection[index].speed[1];
+            self.collection[index].pace(-speed);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.collection.len()
+    }
+
+    /// Whether a crab, in play or not, is a king
+    pub fn is_king(&self, id: usize) -> bool {
+        self.kings.contains(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.collection.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Crab> {
+        self.collection.iter()
+    }
+
+    // Crab standing in a cell
+    fn find(&mut self, position: [usize; 2]) -> Option<&mut Crab> {
+        self.collection
+            .iter_mut()
+            .find(|crab| crab.position == position)
+    }
+
+    /// Change the order crabs are advanced in
+    pub(crate) fn shuffle(&mut self, rng: &mut Rng) {
+        for index in (1..self.collection.len()).rev() {
+            self.collection.swap(index, rng.below(index + 1));
         }
     }
 }
 
-#[derive(Debug, Clone)]
-struct Crab {
+/// Why a crab didn't make it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cause {
+    /// Walked or fell into a lethal tile
+    Hazard(Scenery),
+    /// Landed after falling too far at terminal velocity
+    Fall,
+    /// Crossed an edge of the map that kills
+    OutOfBounds,
+    /// Left through the named exit, which is for another team
+    WrongExit(String),
+    /// Left through the named exit after it was full
+    ExitFull(String),
+}
+
+/// What became of a crab after it has been advanced
+#[derive(Debug, Clone, PartialEq)]
+enum Fate {
+    Alive,
+    /// Reached the safety tile at this cell
+    Rescued([usize; 2]),
+    Died(Cause),
+}
+
+/// Species of crab, each getting about in its own way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+    /// Follows the usual rules
+    #[default]
+    Common,
+    /// Breaks any trampoline it lands on
+    Heavy,
+    /// Climbs walls a single block high, as if they were wedges
+    Climber,
+    /// Falls a cell per tick at most, so survives any fall
+    Floater,
+    /// Must reach safety, or the level is lost
+    King,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [
+        Self::Common,
+        Self::Heavy,
+        Self::Climber,
+        Self::Floater,
+        Self::King,
+    ];
+
+    pub fn is_common(&self) -> bool {
+        *self == Self::Common
+    }
+
+    /// Glyph the crab is drawn with
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::Common => '.',
+            Self::Heavy => 'O',
+            Self::Climber => 'Y',
+            Self::Floater => '*',
+            Self::King => 'K',
+        }
+    }
+
+    /// Kind of crab drawn with this glyph, if any
+    pub fn from_char(glyph: char) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.to_char() == glyph)
+    }
+
+    // Fastest the crab can fall, in cells per tick
+    fn terminal(&self) -> isize {
+        match self {
+            Self::Floater => 1,
+            _ => VMAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Crab {
+    id: usize,
+    kind: Kind,
+    team: Option<Team>,
     position: [usize; 2],
     velocity: [isize; 2],
+    // Position within the cell [y, x] under continuous physics, from 0 to SCALE - 1
+    offset: [isize; 2],
+    // Velocity [vy, vx] in 1/SCALE of a cell per tick, kept in step with `velocity`
+    speed: [isize; 2],
+    // Number of cells fallen at terminal velocity
+    falling: usize,
+    // Cells moved through during the current tick
+    path: Vec<[usize; 2]>,
+    // Cells of other crabs run into during the current tick
+    bumped: Vec<[usize; 2]>,
+    // Ticks until the crab can teleport again
+    cooldown: usize,
+    // Job the player gave the crab
+    job: Option<Job>,
+    // Changes to the scenery made during the current tick (cell, from, to), which are made
+    // once every crab has moved
+    rework: Vec<([usize; 2], Scenery, Scenery)>,
 }
 
 impl Crab {
-    fn new(position: [usize; 2], velocity: [isize; 2]) -> Self {
-        Crab { position, velocity }
+    fn new(id: usize, position: [usize; 2], velocity: [isize; 2], kind: Kind) -> Self {
+        Crab {
+            id,
+            kind,
+            team: None,
+            position,
+            velocity,
+            // Crabs start at the bottom of their cell, so they are standing on whatever is below
+            offset: [SCALE - 1, SCALE / 2],
+            speed: [velocity[0] * SCALE, velocity[1] * SCALE],
+            falling: 0,
+            path: Vec::new(),
+            bumped: Vec::new(),
+            cooldown: 0,
+            job: None,
+            rework: Vec::new(),
+        }
+    }
+
+    /// Index of this crab in the level it was loaded from
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn team(&self) -> Option<Team> {
+        self.team
+    }
+
+    /// Position [y, x]
+    pub fn position(&self) -> [usize; 2] {
+        self.position
+    }
+
+    /// Velocity [vy, vx] (in whole cells per tick, rounded towards zero under continuous physics)
+    pub fn velocity(&self) -> [isize; 2] {
+        self.velocity
     }
 
-    fn advance(&mut self, map: &mut Map) -> bool {
+    /// Position within the cell [y, x] under continuous physics, in 1/SCALE of a cell
+    pub fn offset(&self) -> [isize; 2] {
+        self.offset
+    }
+
+    /// Velocity [vy, vx] in 1/SCALE of a cell per tick
+    pub fn speed(&self) -> [isize; 2] {
+        self.speed
+    }
+
+    // Set the horizontal speed, in 1/SCALE of a cell per tick
+    fn pace(&mut self, speed: isize) {
+        self.speed[1] = speed;
+        self.velocity[1] = speed / SCALE;
+    }
+
+    fn advance(&mut self, map: &mut Map) -> Fate {
         // Remove previous position from map
-        map.overide(&self.position, Scenery::Empty);
+        let start = self.position;
+        map.lift(start);
+        self.path.clear();
+        self.bumped.clear();
+        self.cooldown = self.cooldown.saturating_sub(1);
+
+        let fate = match self.ride(map) {
+            Fate::Alive => self.travel(map),
+            fate => fate,
+        };
+
+        // Other crabs still see this one where it started, until every crab has moved
+        map.stamp(start);
+
+        // Did this crab make it to safety (or meet a sticky end)?
+        fate
+    }
 
-        // Evaluate x direction first (no diagonal motion!)
-        let safe_x = self.advance_one_step_x(map, cmp::max(1, self.velocity[1].abs() as usize));
+    // Move under its own steam
+    fn travel(&mut self, map: &mut Map) -> Fate {
+        match map.physics.continuous {
+            Some(rules) => {
+                let fate = self.glide(map, rules);
+                self.velocity = [self.speed[0] / SCALE, self.speed[1] / SCALE];
+                fate
+            }
+            None => {
+                // Evaluate x direction first (no diagonal motion!)
+                let mut fate = self.advance_one_step_x(map, self.velocity[1].unsigned_abs().max(1));
 
-        // Evaluate y direction
-        let safe_y = self.advance_one_step_y(map, cmp::max(1, self.velocity[0].abs() as usize));
+                // Evaluate y direction
+                if fate == Fate::Alive {
+                    fate = self.advance_one_step_y(map, self.velocity[0].unsigned_abs().max(1));
+                }
+                self.speed = [self.velocity[0] * SCALE, self.velocity[1] * SCALE];
+                fate
+            }
+        }
+    }
+
+    fn enter(&mut self, map: &mut Map, next: [usize; 2]) {
+        self.position = next;
+        self.path.push(next);
+        map.visit(next);
+        self.teleport(map);
+    }
 
-        // Add new position to map
-        map.update(&self.position, Scenery::StationaryCrab);
+    // Climbers go over a block with space above it, though not over the edge of the map
+    fn scales(&self, map: &Map, next: [usize; 2]) -> bool {
+        self.kind == Kind::Climber
+            && next != self.position
+            && map.ahead(next, [-1, 0]).map(|(_, above)| above) == Ok(Scenery::Empty)
+    }
 
-        // Did this crab make it to safety?
-        safe_x || safe_y
+    /// How many cells this crab moved before reaching one the other crab moved through
+    fn arrival(&self, other: &Crab) -> Option<usize> {
+        self.path.iter().position(|cell| other.path.contains(cell))
     }
 
-    fn advance_one_step_x(&mut self, map: &Map, steps: usize) -> bool {
+    fn advance_one_step_x(&mut self, map: &mut Map, steps: usize) -> Fate {
         match steps {
             0 => (),
             n => {
                 // Find next position along direction
-                let mut next = self.position.clone();
-                map.wrap(&mut next, [0, self.velocity[1].signum()]);
+                let (next, scenery) = match map.ahead(self.position, [0, self.velocity[1].signum()])
+                {
+                    Ok(ahead) => ahead,
+                    Err(cause) => return Fate::Died(cause),
+                };
 
                 // Determine if obstacles are present
-                match map.layout[next[0]][next[1]] {
+                match scenery {
                     Scenery::Empty => {
                         // Move into empty space
-                        self.position = next;
-                        self.advance_one_step_x(map, n - 1);
+                        self.enter(map, next);
+                        return self.advance_one_step_x(map, n - 1);
                     }
-                    Scenery::ForwardWedge => {
+                    Scenery::ForwardWedge if self.velocity[1] > 0 => {
                         // Advance up wedge
-                        if self.velocity[1] > 0 {
-                            let tmp_vel = self.velocity[0];
-                            let tmp_pos = self.position[0];
-                            self.velocity[0] = -2; // overcome gravity
-                            self.advance_one_step_y(map, 1);
-                            self.velocity[0] = tmp_vel;
-                            if self.position[0] == tmp_pos {
-                                // Rebound
-                                self.velocity[1] *= -1;
-                            } else {
-                                self.advance_one_step_x(map, n);
-                            }
-                        } else {
-                            // Rebound
-                            self.velocity[1] *= -1;
-                        }
+                        return self.climb(map, n);
                     }
-                    Scenery::BackwardWedge => {
+                    Scenery::BackwardWedge if self.velocity[1] < 0 => {
                         // Advance up wedge
-                        if self.velocity[1] < 0 {
-                            let tmp_vel = self.velocity[0];
-                            let tmp_pos = self.position[0];
-                            self.velocity[0] = -2; // overcome gravity
-                            self.advance_one_step_y(map, 1);
-                            self.velocity[0] = tmp_vel;
-                            if self.position[0] == tmp_pos {
-                                // Rebound
-                                self.velocity[1] *= -1;
-                            } else {
-                                self.advance_one_step_x(map, n);
-                            }
-                        } else {
-                            // Rebound
-                            self.velocity[1] *= -1;
-                        }
+                        return self.climb(map, n);
+                    }
+                    Scenery::Block if self.scales(map, next) => {
+                        // Climb over the wall
+                        return self.climb(map, n);
+                    }
+                    pad @ (Scenery::ForwardBoost | Scenery::BackwardBoost) => {
+                        // Cross the speed pad, which sends the crab its way a little faster
+                        self.enter(map, next);
+                        self.boost(pad);
+                        return self.advance_one_step_x(map, n - 1);
                     }
-                    //                    Scenery::ForwardBoost => {
-                    //                        // Speed boost forward
-                    //                        if self.velocity[1].abs() < VMAX {
-                    //                            self.velocity[1] += 1;
-                    //                        }
-                    //                        self.position = next;
-                    //                        self.advance_one_step_x(map, n - 1);
-                    //                    }
-                    //                    Scenery::BackwardBoost => {
-                    //                        // Speed boost backward
-                    //                        if self.velocity[1].abs() < VMAX {
-                    //                            self.velocity[1] -= 1;
-                    //                        }
-                    //                        self.position = next;
-                    //                        self.advance_one_step_x(map, n - 1);
-                    //                    }
                     Scenery::Safety => {
                         // the crab made it to safety!
-                        return true;
+                        return Fate::Rescued(next);
+                    }
+                    hazard @ (Scenery::Spikes | Scenery::Water | Scenery::Lava) => {
+                        return Fate::Died(Cause::Hazard(hazard));
+                    }
+                    Scenery::StationaryCrab => {
+                        // Stop behind another crab (speeds are exchanged once every crab has moved)
+                        self.bumped.push(next);
                     }
                     _ => {
                         // Rebound
@@ -269,67 +611,464 @@ impl Crab {
                 }
             }
         }
-        false
+        Fate::Alive
+    }
+
+    /// Speed pads send a crab their way, one cell per tick faster (up to VMAX)
+    fn boost(&mut self, pad: Scenery) {
+        self.velocity[1] = pad.heading() * (self.velocity[1].abs() + 1).min(VMAX);
     }
 
-    fn advance_one_step_y(&mut self, map: &Map, steps: usize) -> bool {
+    fn climb(&mut self, map: &mut Map, steps: usize) -> Fate {
+        let tmp_vel = self.velocity[0];
+        let tmp_pos = self.position[0];
+        self.velocity[0] = -2; // overcome gravity
+        let fate = self.advance_one_step_y(map, 1);
+        self.velocity[0] = tmp_vel;
+        if fate != Fate::Alive {
+            fate
+        } else if self.position[0] == tmp_pos {
+            // Rebound
+            self.velocity[1] *= -1;
+            Fate::Alive
+        } else {
+            self.advance_one_step_x(map, steps)
+        }
+    }
+
+    fn advance_one_step_y(&mut self, map: &mut Map, steps: usize) -> Fate {
         match steps {
             0 => (),
             n => {
-                // Acceleration due to gravity
-                if self.velocity[0] < VMAX {
+                // Acceleration due to gravity (floaters drift down a cell at a time)
+                if self.velocity[0] < self.kind.terminal() {
                     self.velocity[0] += 1;
+                } else if self.kind == Kind::Floater {
+                    self.velocity[0] = self.kind.terminal();
                 }
 
                 // Find next position along direction
-                let mut next = self.position.clone();
-                map.wrap(&mut next, [self.velocity[0].signum(), 0]);
-
-                // Determine if obstacles are present
-                match map.layout[next[0]][next[1]] {
+                let (next, scenery) = match map.ahead(self.position, [self.velocity[0].signum(), 0])
+                {
+                    Ok(ahead) => ahead,
+                    Err(cause) => return Fate::Died(cause),
+                };
+
+                // Determine if obstacles are present (crabs stand on each other)
+                match scenery {
                     // Move into empty space
                     Scenery::Empty => {
-                        self.position = next;
-                        self.advance_one_step_y(map, n - 1);
+                        self.enter(map, next);
+                        if self.velocity[0] == VMAX {
+                            self.falling += 1;
+                        } else {
+                            self.falling = 0;
+                        }
+                        return self.advance_one_step_y(map, n - 1);
+                    }
+                    Scenery::Trampoline if self.kind == Kind::Heavy && self.velocity[0] > 0 => {
+                        // Land on the trampoline, breaking it
+                        self.rework
+                            .push((next, Scenery::Trampoline, Scenery::Empty));
+                        self.velocity[0] = 0;
+                        self.falling = 0;
                     }
                     Scenery::Trampoline => {
                         self.velocity[0] = -VMAX;
+                        self.falling = 0;
                     }
-                    Scenery::Safety => return true,
-                    _ => {
+                    Scenery::Safety => return Fate::Rescued(next),
+                    hazard @ (Scenery::Spikes | Scenery::Water | Scenery::Lava) => {
+                        return Fate::Died(Cause::Hazard(hazard));
+                    }
+                    floor => {
                         if self.velocity[0] < 0 {
                             // Rebound above
                             self.velocity[0] *= -1;
                         } else {
-                            // Stop below
+                            // Stop below, unless the fall was too far
+                            if self.falling > map.physics.fall_limit {
+                                return Fate::Died(Cause::Fall);
+                            }
                             self.velocity[0] = 0;
+
+                            // Landing or standing on a speed pad kicks the crab along
+                            if floor.is_boost() {
+                                self.boost(floor);
+                            }
                         }
+                        self.falling = 0;
+                    }
+                }
+            }
+        }
+        Fate::Alive
+    }
+
+    /// Move under continuous physics, along a straight line through the cells for this tick.
+    ///
+    /// The line is split into pieces less than a cell long, moving x then y in each, so crabs
+    /// travel diagonally and follow arcs (but still turn corners the same way every time).
+    fn glide(&mut self, map: &mut Map, rules: Continuous) -> Fate {
+        // Acceleration due to gravity
+        self.speed[0] = (self.speed[0] + rules.gravity).min(self.kind.terminal() * SCALE);
+
+        let distance = [self.speed[0].abs(), self.speed[1].abs()];
+        let pieces = distance[0].max(distance[1]) / SCALE + 1;
+        let mut moving = [true, true];
+        for piece in 1..=pieces {
+            for &axis in [1, 0].iter() {
+                if !moving[axis] {
+                    continue;
+                }
+                let length =
+                    distance[axis] * piece / pieces - distance[axis] * (piece - 1) / pieces;
+                match self.slide(map, rules, axis, length) {
+                    Ok(carry_on) => moving[axis] = carry_on,
+                    Err(fate) => return fate,
+                }
+            }
+        }
+        Fate::Alive
+    }
+
+    // Move part of a cell along one axis, dealing with whatever is in the next cell if the crab
+    // crosses into it. Returns whether the crab can carry on along this axis during the tick.
+    fn slide(
+        &mut self,
+        map: &mut Map,
+        rules: Continuous,
+        axis: usize,
+        length: isize,
+    ) -> Result<bool, Fate> {
+        let direction = self.speed[axis].signum();
+        let offset = self.offset[axis] + direction * length;
+        if (0..SCALE).contains(&offset) {
+            self.offset[axis] = offset;
+            return Ok(direction != 0);
+        }
+
+        let mut change = [0, 0];
+        change[axis] = direction;
+        let (next, scenery) = map.ahead(self.position, change).map_err(Fate::Died)?;
+        // Where the crab stops if it can't go any further
+        let edge = if direction > 0 { SCALE - 1 } else { 0 };
+
+        match (axis, scenery) {
+            (_, Scenery::Empty) => {
+                self.offset[axis] = offset - direction * SCALE;
+                self.enter(map, next);
+                if axis == 0 {
+                    if self.speed[0] >= VMAX * SCALE {
+                        self.falling += 1;
+                    } else {
+                        self.falling = 0;
+                    }
+                }
+                Ok(true)
+            }
+            (_, Scenery::Safety) => Err(Fate::Rescued(next)),
+            (_, hazard @ (Scenery::Spikes | Scenery::Water | Scenery::Lava)) => {
+                Err(Fate::Died(Cause::Hazard(hazard)))
+            }
+            (1, Scenery::ForwardWedge) if direction > 0 => self.mount(map, rules, length),
+            (1, Scenery::BackwardWedge) if direction < 0 => self.mount(map, rules, length),
+            (1, Scenery::Block) if self.scales(map, next) => self.mount(map, rules, length),
+            (1, pad @ (Scenery::ForwardBoost | Scenery::BackwardBoost)) => {
+                self.offset[1] = offset - direction * SCALE;
+                self.enter(map, next);
+                self.spur(pad);
+                Ok(true)
+            }
+            (1, Scenery::StationaryCrab) => {
+                // Stop behind another crab (speeds are exchanged once every crab has moved)
+                self.offset[1] = edge;
+                self.bumped.push(next);
+                Ok(false)
+            }
+            (1, _) => {
+                // Rebound
+                self.offset[1] = edge;
+                self.speed[1] *= -1;
+                Ok(false)
+            }
+            (_, Scenery::Trampoline) if direction > 0 && self.kind == Kind::Heavy => {
+                // Land on the trampoline, breaking it
+                self.offset[0] = edge;
+                self.rework
+                    .push((next, Scenery::Trampoline, Scenery::Empty));
+                self.speed[0] = 0;
+                self.falling = 0;
+                Ok(false)
+            }
+            (_, Scenery::Trampoline) if direction > 0 => {
+                // Bounce back as fast as the crab came down, and always at least a cell per tick
+                self.offset[0] = edge;
+                self.speed[0] = -self.speed[0].max(SCALE);
+                self.falling = 0;
+                Ok(false)
+            }
+            (_, floor) => {
+                self.offset[0] = edge;
+                let bounce = self.speed[0].abs() * rules.restitution / SCALE;
+                if direction < 0 {
+                    // Rebound off the ceiling
+                    self.speed[0] = bounce;
+                } else {
+                    // Land, unless the fall was too far
+                    if self.falling > map.physics.fall_limit {
+                        return Err(Fate::Died(Cause::Fall));
+                    }
+                    // Small bounces die away, so crabs settle on the ground
+                    self.speed[0] = if bounce > rules.gravity { -bounce } else { 0 };
+                    self.pace(self.speed[1] * (SCALE - rules.friction) / SCALE);
+
+                    // Landing or standing on a speed pad kicks the crab along
+                    if floor.is_boost() {
+                        self.spur(floor);
                     }
                 }
+                self.falling = 0;
+                Ok(false)
             }
         }
-        false
     }
+
+    // Speed pads under continuous physics, one cell per tick faster (up to VMAX)
+    fn spur(&mut self, pad: Scenery) {
+        self.pace(pad.heading() * (self.speed[1].abs() + SCALE).min(VMAX * SCALE));
+    }
+
+    // Step up onto a wedge, then carry on across
+    fn mount(&mut self, map: &mut Map, rules: Continuous, length: isize) -> Result<bool, Fate> {
+        let (above, scenery) = map.ahead(self.position, [-1, 0]).map_err(Fate::Died)?;
+        match scenery {
+            Scenery::Empty => {
+                self.enter(map, above);
+                self.offset[0] = SCALE - 1;
+                self.falling = 0;
+                self.slide(map, rules, 1, length)
+            }
+            Scenery::Safety => Err(Fate::Rescued(above)),
+            hazard @ (Scenery::Spikes | Scenery::Water | Scenery::Lava) => {
+                Err(Fate::Died(Cause::Hazard(hazard)))
+            }
+            _ => {
+                // Rebound
+                self.offset[1] = if self.speed[1] > 0 { SCALE - 1 } else { 0 };
+                self.speed[1] *= -1;
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Rules of motion that can be adjusted per level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Physics {
+    /// Cells a crab can fall at terminal velocity and survive the landing
+    pub fall_limit: usize,
+    /// Sub-cell motion, rather than moving crabs a whole cell at a time
+    pub continuous: Option<Continuous>,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            fall_limit: FALL_LIMIT,
+            continuous: None,
+        }
+    }
+}
+
+/// Settings for continuous physics, in fixed point (1/SCALE of a cell, or a fraction out of SCALE).
+///
+/// Crabs keep a position within their cell and move along straight lines each tick, so they
+/// fly in arcs under gravity. They are still drawn in whichever cell they are in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Continuous {
+    /// Speed gained falling each tick
+    pub gravity: isize,
+    /// Fraction of horizontal speed lost each tick a crab is on the ground
+    pub friction: isize,
+    /// Fraction of vertical speed kept when a crab bounces off a floor or ceiling
+    pub restitution: isize,
+}
+
+impl Default for Continuous {
+    fn default() -> Self {
+        Continuous {
+            gravity: SCALE / 4,
+            friction: 0,
+            restitution: 0,
+        }
+    }
+}
+
+/// What happens to a crab that reaches an edge of the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Boundary {
+    /// Come back in on the opposite edge
+    Wrap,
+    /// Solid, just like a block
+    Wall,
+    /// Crabs that cross it are lost
+    Kill,
+}
+
+/// Boundary at each edge of the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Boundaries {
+    #[serde(default = "wrap")]
+    pub top: Boundary,
+    #[serde(default = "wrap")]
+    pub bottom: Boundary,
+    #[serde(default = "wrap")]
+    pub left: Boundary,
+    #[serde(default = "wrap")]
+    pub right: Boundary,
+}
+
+fn wrap() -> Boundary {
+    Boundary::Wrap
+}
+
+impl Default for Boundaries {
+    fn default() -> Self {
+        Boundaries {
+            top: Boundary::Wrap,
+            bottom: Boundary::Wrap,
+            left: Boundary::Wrap,
+            right: Boundary::Wrap,
+        }
+    }
+}
+
+/// Scenery the player may place, and how much of it is left
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inventory {
+    // No budget means unlimited placements
+    budget: Option<HashMap<Scenery, usize>>,
+}
+
+impl Inventory {
+    /// Allow any amount of scenery to be placed
+    pub fn unlimited() -> Self {
+        Inventory { budget: None }
+    }
+
+    /// Only allow the given number of each tile (anything missing can't be placed at all)
+    pub fn limited(budget: HashMap<Scenery, usize>) -> Self {
+        Inventory {
+            budget: Some(budget),
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.budget.is_some()
+    }
+
+    /// Number of tiles of this kind left to place (None if unlimited)
+    pub fn remaining(&self, scenery: Scenery) -> Option<usize> {
+        self.budget
+            .as_ref()
+            .map(|budget| budget.get(&scenery).copied().unwrap_or(0))
+    }
+
+    /// Remaining counts for each placeable tile in the budget, in key order
+    pub fn counts(&self) -> Vec<(Scenery, usize)> {
+        Scenery::PLACEABLE
+            .iter()
+            .filter_map(|&scenery| {
+                self.budget
+                    .as_ref()
+                    .and_then(|budget| budget.get(&scenery))
+                    .map(|&count| (scenery, count))
+            })
+            .collect()
+    }
+
+    fn take(&mut self, scenery: Scenery) -> bool {
+        match self.budget.as_mut() {
+            None => true,
+            Some(budget) => match budget.get_mut(&scenery) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    true
+                }
+                _ => false,
+            },
+        }
+    }
+
+    fn give(&mut self, scenery: Scenery) {
+        if let Some(budget) = self.budget.as_mut() {
+            *budget.entry(scenery).or_insert(0) += 1;
+        }
+    }
+}
+
+/// Reason scenery couldn't be placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// Something is already there
+    Occupied,
+    /// None of this tile (or skill) left
+    OutOfStock,
+    /// No crab to give a skill to
+    NoCrab,
+    /// Crab already has a job
+    Busy,
+    /// Only scenery the player placed can be taken away
+    NotPlaced,
+    /// Position is off the edge of the map
+    OutOfBounds,
+}
+
+/// Where the scenery in a cell came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// Part of the level as it was designed
+    Level,
+    /// Placed by the player
+    Player,
+    /// Changed by the crabs (by breaking, digging or building)
+    Crab,
 }
 
 #[derive(Debug, Clone)]
 pub struct Map {
     pub dimensions: [usize; 2],
+    pub physics: Physics,
+    pub inventory: Inventory,
+    pub boundaries: Boundaries,
+    /// Named safety tiles
+    pub exits: Vec<Exit>,
+    /// Pressure plates and the doors they switch
+    pub links: Vec<Link>,
+    /// Pairs of teleporter pads
+    pub teleporters: Vec<Teleporter>,
+    // Teleporter pad placed by the player that is still waiting for its partner
+    unpaired: Option<[usize; 2]>,
+    // Where the scenery in each cell came from
+    origins: Vec<Vec<Origin>>,
     layout: Vec<Vec<Scenery>>,
     index: [usize; 2],
+    // Cells crabs have moved through, when being tracked
+    trail: Option<HashSet<[usize; 2]>>,
+    // Cells with a crab in them (crabs on speed pads can't be seen in the layout)
+    crabs: HashSet<[usize; 2]>,
 }
 
 impl Map {
     pub fn new(cmap: &str) -> Self {
         // Determine size of layout
         let y_size = cmap.lines().count();
-        let x_size = cmap.lines().nth(0).unwrap().chars().count();
-        eprintln!("Level dimensions: (y, x) = ({}, {})", y_size, x_size);
+        let x_size = cmap.lines().next().map_or(0, |line| line.chars().count());
         let dimensions = [y_size, x_size];
 
         // Allocate vector to store layout
         let mut layout = vec![vec![Scenery::Empty; x_size]; y_size];
-        eprintln!("Map allocated: ({},{})", layout.len(), layout[0].len());
 
         // Fill in scenery
         for (yvec, line) in layout.iter_mut().zip(cmap.lines()) {
@@ -340,44 +1079,157 @@ impl Map {
 
         Map {
             dimensions,
+            physics: Physics::default(),
+            inventory: Inventory::unlimited(),
+            boundaries: Boundaries::default(),
+            exits: Vec::new(),
+            links: Vec::new(),
+            teleporters: Vec::new(),
+            unpaired: None,
+            origins: vec![vec![Origin::Level; x_size]; y_size],
             layout,
             index: [0, 0],
+            trail: None,
+            crabs: HashSet::new(),
         }
     }
 
-    pub fn instantaneous(&mut self, entities: &Entities) {
-        // Fill in positions of crabs
+    /// Fill in positions of crabs, returning the IDs of any that overlap something else
+    pub fn instantaneous(&mut self, entities: &Entities) -> Vec<usize> {
+        let mut embedded: Vec<usize> = Vec::new();
         for entity in entities.collection.iter() {
-            let y: usize = entity.position[0];
-            let x: usize = entity.position[1];
+            let [y, x] = entity.position;
 
-            if self.layout[y][x] == Scenery::Empty {
-                self.layout[y][x] = Scenery::StationaryCrab;
+            // Crabs can stop part way across a speed pad
+            if self.crabs.contains(&entity.position) || !self.layout[y][x].is_passable() {
+                // Crab has been placed inside scenery (or on top of another crab)
+                embedded.push(entity.id);
             } else {
-                // Shouldn't ever reach here!
-                panic!("Oh no, we've lost a crab!");
+                self.stamp(entity.position);
             }
         }
+        embedded
     }
 
     pub fn decrab(&mut self) {
         // Remove crabs from map
-        for yvec in self.layout.iter_mut() {
-            for cell in yvec.iter_mut() {
-                if *cell == Scenery::StationaryCrab {
-                    *cell = Scenery::Empty;
-                }
-            }
+        for cell in self.crabs.clone() {
+            self.lift(cell);
+        }
+    }
+
+    /// What a crab moving into this cell would find
+    fn look(&self, cell: [usize; 2]) -> Scenery {
+        if self.crabs.contains(&cell) {
+            Scenery::StationaryCrab
+        } else {
+            self.layout[cell[0]][cell[1]]
         }
     }
 
-    pub fn update(&mut self, user: &[usize; 2], scenery: Scenery) {
+    // Put a crab on the map, showing it unless it's on a speed pad
+    fn stamp(&mut self, cell: [usize; 2]) {
+        self.crabs.insert(cell);
+        if self.layout[cell[0]][cell[1]] == Scenery::Empty {
+            self.overide(&cell, Scenery::StationaryCrab);
+        }
+    }
+
+    // Change scenery left behind by a crab, if it is still what the crab found
+    fn rework(&mut self, cell: [usize; 2], from: Scenery, to: Scenery) {
+        if self.layout[cell[0]][cell[1]] == from {
+            self.overide(&cell, to);
+            self.origins[cell[0]][cell[1]] = Origin::Crab;
+        }
+    }
+
+    // Take a crab off the map
+    fn lift(&mut self, cell: [usize; 2]) {
+        self.crabs.remove(&cell);
+        if self.layout[cell[0]][cell[1]] == Scenery::StationaryCrab {
+            self.overide(&cell, Scenery::Empty);
+        }
+    }
+
+    /// Add new scenery at desired location (if empty and there is some left in the inventory)
+    pub fn update(&mut self, user: &[usize; 2], scenery: Scenery) -> Result<(), Refusal> {
         let [y, x] = *user;
-        // Add new scenery at desired location (if empty)
-        if self.layout[y][x] == Scenery::Empty {
+        if !self.contains(*user) {
+            Err(Refusal::OutOfBounds)
+        } else if self.layout[y][x] != Scenery::Empty {
+            Err(Refusal::Occupied)
+        } else if !self.inventory.take(scenery) {
+            Err(Refusal::OutOfStock)
+        } else {
             self.layout[y][x] = scenery;
+            self.origins[y][x] = Origin::Player;
+            if scenery == Scenery::Teleporter {
+                self.pair(*user);
+            }
+            Ok(())
+        }
+    }
+
+    /// Take away scenery the player placed, returning it to the inventory
+    pub fn remove(&mut self, user: &[usize; 2]) -> Result<Scenery, Refusal> {
+        let [y, x] = *user;
+        if !self.contains(*user) {
+            return Err(Refusal::OutOfBounds);
+        }
+        let scenery = self.layout[y][x];
+        // Anything the crabs have since changed stays
+        if self.origins[y][x] != Origin::Player {
+            return Err(Refusal::NotPlaced);
+        }
+        self.origins[y][x] = Origin::Level;
+        self.inventory.give(scenery);
+        self.layout[y][x] = if self.crabs.contains(user) {
+            Scenery::StationaryCrab
         } else {
-            eprintln!("Can't place {:?} as something is already there!", scenery);
+            Scenery::Empty
+        };
+        if scenery == Scenery::Teleporter {
+            self.unpair(*user);
+        }
+        Ok(scenery)
+    }
+
+    /// Whether a cell is on the map
+    pub fn contains(&self, cell: [usize; 2]) -> bool {
+        cell[0] < self.dimensions[0] && cell[1] < self.dimensions[1]
+    }
+
+    /// Where the scenery in this cell came from
+    pub fn origin(&self, cell: [usize; 2]) -> Origin {
+        self.origins[cell[0]][cell[1]]
+    }
+
+    /// Scenery the player has placed that is still on the map, row by row
+    pub fn placed(&self) -> Vec<([usize; 2], Scenery)> {
+        let mut placed = Vec::new();
+        for (y, row) in self.origins.iter().enumerate() {
+            for (x, origin) in row.iter().enumerate() {
+                if *origin == Origin::Player {
+                    placed.push(([y, x], self.layout[y][x]));
+                }
+            }
+        }
+        placed
+    }
+
+    /// Start noting every cell a crab moves through
+    pub fn track(&mut self) {
+        self.trail = Some(HashSet::new());
+    }
+
+    /// Cells crabs have moved through since tracking started
+    pub fn trail(&self) -> Option<&HashSet<[usize; 2]>> {
+        self.trail.as_ref()
+    }
+
+    fn visit(&mut self, cell: [usize; 2]) {
+        if let Some(trail) = self.trail.as_mut() {
+            trail.insert(cell);
         }
     }
 
@@ -387,41 +1239,69 @@ impl Map {
         self.layout[y][x] = scenery;
     }
 
-    fn wrap(&self, user: &mut [usize; 2], change: [isize; 2]) {
-        // Cast to signed integeter to avoid overflows
-        let mut tmp_user = [user[0] as isize, user[1] as isize];
-        let tmp_dims = [self.dimensions[0] as isize, self.dimensions[1] as isize];
-
-        // Update coordinates with periodic boundaries
-        for ((user_i, &dim_i), &change_i) in
-            tmp_user.iter_mut().zip(tmp_dims.iter()).zip(change.iter())
-        {
-            *user_i = if *user_i + change_i >= dim_i {
-                // Wrap above
-                *user_i + change_i - dim_i
-            } else if *user_i + change_i < 0 {
-                // Wrap below
-                *user_i + change_i + dim_i
+    /// Cell next to `cell` in direction `change` (one of up, down, left or right), or the
+    /// boundary in the way if it doesn't wrap
+    pub fn neighbour(&self, cell: [usize; 2], change: [isize; 2]) -> Result<[usize; 2], Boundary> {
+        let mut next = cell;
+        for axis in 0..2 {
+            let (before, after) = if axis == 0 {
+                (self.boundaries.top, self.boundaries.bottom)
             } else {
-                // Otherwise change as normal
-                *user_i + change_i
+                (self.boundaries.left, self.boundaries.right)
+            };
+            let size = self.dimensions[axis];
+            next[axis] = match change[axis] {
+                -1 if cell[axis] == 0 => match before {
+                    Boundary::Wrap => size - 1,
+                    boundary => return Err(boundary),
+                },
+                1 if cell[axis] + 1 == size => match after {
+                    Boundary::Wrap => 0,
+                    boundary => return Err(boundary),
+                },
+                -1 => cell[axis] - 1,
+                1 => cell[axis] + 1,
+                _ => cell[axis],
             };
         }
+        Ok(next)
+    }
 
-        // Recast back to unsigned index
-        *user = [tmp_user[0] as usize, tmp_user[1] as usize];
+    // Cell a crab would move into and what it would find there (walls are as solid as blocks,
+    // and teleporters, open doors and gates the crab's way are as good as empty space)
+    fn ahead(&self, cell: [usize; 2], change: [isize; 2]) -> Result<([usize; 2], Scenery), Cause> {
+        match self.neighbour(cell, change) {
+            Ok(next) => Ok((
+                next,
+                match self.look(next) {
+                    Scenery::Teleporter => Scenery::Empty,
+                    door @ Scenery::Door { .. } if door.is_passable() => Scenery::Empty,
+                    gate @ Scenery::Gate { .. } if change[1] == gate.heading() => Scenery::Empty,
+                    scenery => scenery,
+                },
+            )),
+            Err(Boundary::Kill) => Err(Cause::OutOfBounds),
+            Err(_) => Ok((cell, Scenery::Block)),
+        }
     }
 
-    fn to_string(&self) -> String {
-        let mut buffer = String::new();
-        for (_, x, ch) in self.clone() {
-            buffer.push(ch);
-            if x == self.dimensions[1] - 1 {
-                buffer.push('\\');
-                buffer.push('n');
+    // Move the cursor, which stops at edges that don't wrap
+    fn nudge(&self, user: &mut [usize; 2], change: [isize; 2]) {
+        if let Ok(next) = self.neighbour(*user, change) {
+            *user = next;
+        }
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (y, x, ch) in self.clone() {
+            write!(f, "{}", ch)?;
+            if x + 1 == self.dimensions[1] && y + 1 < self.dimensions[0] {
+                writeln!(f)?;
             }
         }
-        buffer
+        Ok(())
     }
 }
 
@@ -429,7 +1309,9 @@ impl Iterator for Map {
     type Item = (usize, usize, char);
 
     fn next(&mut self) -> Option<(usize, usize, char)> {
-        if (self.index[1]*self.dimensions[0] + self.index[0]) < self.dimensions[0] * self.dimensions[1] {
+        if (self.index[1] * self.dimensions[0] + self.index[0])
+            < self.dimensions[0] * self.dimensions[1]
+        {
             // Current position
             let y = self.index[0];
             let x = self.index[1];
@@ -456,30 +1338,118 @@ impl Iterator for Map {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Scenery {
     Empty,
     Block,
     ForwardWedge,
     BackwardWedge,
+    /// Speed pads send crabs their way, one cell per tick faster (up to VMAX), whenever a crab
+    /// crosses one or lands or stands on one. Crabs moving against a pad are turned around.
+    /// Pads are solid from below: one directly above a crab stops it climbing a wedge, but
+    /// one directly above a wedge is crossed as the crab reaches the top.
     ForwardBoost,
     BackwardBoost,
     Trampoline,
     Safety,
+    Spikes,
+    Water,
+    Lava,
+    /// Hatch crabs are released from (see `Spawner`), solid like a block
+    Spawner,
+    /// Pressure plate, solid like a block, that switches its linked doors (see `Link`) while a
+    /// crab stands on it
+    Plate,
+    /// Door, built open or shut, held the other way while one of its plates is pressed.
+    /// Crabs pass through open doors, and a door doesn't shut on a crab.
+    Door {
+        open: bool,
+        toggled: bool,
+    },
+    /// Conveyor belt, solid like a block, carrying any crab standing on it a cell each tick
+    Conveyor {
+        forward: bool,
+    },
+    /// One-way gate, which crabs can only walk through one way, and is solid otherwise
+    Gate {
+        forward: bool,
+    },
+    /// Teleporter pad, sending crabs that move onto it to its partner (see `Teleporter`)
+    Teleporter,
     StationaryCrab,
 }
 
 impl Scenery {
+    /// Tiles that the player can place during a level
+    pub const PLACEABLE: [Scenery; 7] = [
+        Self::Block,
+        Self::ForwardWedge,
+        Self::BackwardWedge,
+        Self::Trampoline,
+        Self::ForwardBoost,
+        Self::BackwardBoost,
+        Self::Teleporter,
+    ];
+
+    /// Speed pads are crossed rather than bumped into
+    pub fn is_boost(&self) -> bool {
+        matches!(self, Self::ForwardBoost | Self::BackwardBoost)
+    }
+
+    /// Whether a crab can be in this cell
+    pub fn is_passable(&self) -> bool {
+        match self {
+            Self::Empty | Self::Gate { .. } | Self::Teleporter => true,
+            Self::Door { open, toggled } => open != toggled,
+            scenery => scenery.is_boost(),
+        }
+    }
+
+    // Direction a speed pad, conveyor belt or gate sends crabs
+    fn heading(&self) -> isize {
+        match self {
+            Self::ForwardBoost
+            | Self::Conveyor { forward: true }
+            | Self::Gate { forward: true } => 1,
+            _ => -1,
+        }
+    }
+
+    /// Scenery for a glyph in a layout (anything unrecognised is empty space)
     pub fn new(scenery: char) -> Self {
+        Self::from_char(scenery).unwrap_or(Self::Empty)
+    }
+
+    /// Scenery for a glyph in a layout, if it is one
+    pub fn from_char(scenery: char) -> Option<Self> {
         match scenery {
-            '#' => Self::Block,
-            '/' => Self::ForwardWedge,
-            '\\' => Self::BackwardWedge,
-            '>' => Self::ForwardBoost,
-            '<' => Self::BackwardBoost,
-            '@' => Self::Trampoline,
-            'X' => Self::Safety,
-            _ => Self::Empty,
+            ' ' => Some(Self::Empty),
+            '#' => Some(Self::Block),
+            '/' => Some(Self::ForwardWedge),
+            '\\' => Some(Self::BackwardWedge),
+            '>' => Some(Self::ForwardBoost),
+            '<' => Some(Self::BackwardBoost),
+            '@' => Some(Self::Trampoline),
+            'X' => Some(Self::Safety),
+            '^' => Some(Self::Spikes),
+            '~' => Some(Self::Water),
+            '%' => Some(Self::Lava),
+            'H' => Some(Self::Spawner),
+            '_' => Some(Self::Plate),
+            'D' => Some(Self::Door {
+                open: false,
+                toggled: false,
+            }),
+            'd' => Some(Self::Door {
+                open: true,
+                toggled: false,
+            }),
+            '}' => Some(Self::Conveyor { forward: true }),
+            '{' => Some(Self::Conveyor { forward: false }),
+            ')' => Some(Self::Gate { forward: true }),
+            '(' => Some(Self::Gate { forward: false }),
+            'T' => Some(Self::Teleporter),
+            _ => None,
         }
     }
 
@@ -493,6 +1463,19 @@ impl Scenery {
             Self::BackwardBoost => '<',
             Self::Trampoline => '@',
             Self::Safety => 'X',
+            Self::Spikes => '^',
+            Self::Water => '~',
+            Self::Lava => '%',
+            Self::Spawner => 'H',
+            Self::Plate => '_',
+            // Doors show whether they are open now
+            Self::Door { open, toggled } if open == toggled => 'D',
+            Self::Door { .. } => 'd',
+            Self::Conveyor { forward: true } => '}',
+            Self::Conveyor { forward: false } => '{',
+            Self::Gate { forward: true } => ')',
+            Self::Gate { forward: false } => '(',
+            Self::Teleporter => 'T',
             Self::StationaryCrab => '.',
         }
     }
@@ -513,64 +1496,91 @@ impl Colour for char {
             '>' => format!("{}", color::Fg(color::Yellow)),
             '@' => format!("{}", color::Fg(color::Cyan)),
             'X' => format!("{}", color::Fg(color::Reset)),
+            '^' => format!("{}", color::Fg(color::LightWhite)),
+            '~' => format!("{}", color::Fg(color::Blue)),
+            '%' => format!("{}", color::Fg(color::LightRed)),
+            'H' => format!("{}", color::Fg(color::Green)),
+            '_' => format!("{}", color::Fg(color::LightMagenta)),
+            'D' => format!("{}", color::Fg(color::LightMagenta)),
+            'd' => format!("{}", color::Fg(color::LightMagenta)),
+            '{' => format!("{}", color::Fg(color::LightBlue)),
+            '}' => format!("{}", color::Fg(color::LightBlue)),
+            '(' => format!("{}", color::Fg(color::White)),
+            ')' => format!("{}", color::Fg(color::White)),
+            'T' => format!("{}", color::Fg(color::LightCyan)),
             '.' => format!("{}", color::Fg(color::Reset)),
+            'O' => format!("{}", color::Fg(color::Magenta)),
+            'Y' => format!("{}", color::Fg(color::LightGreen)),
+            '*' => format!("{}", color::Fg(color::LightCyan)),
+            'K' => format!("{}", color::Fg(color::LightYellow)),
             _ => format!("{}", color::Fg(color::Reset)),
         }
     }
 }
 
-pub fn user_input(
-    key: Key,
-    user: &mut [usize; 2],
-    complete: &mut bool,
-    reset: &mut bool,
-    map: &mut Map,
-) {
+/// What the player asked for with a key press
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Edit(Edit),
+    Reset,
+    Quit,
+}
+
+pub fn user_input(key: Key, user: &mut [usize; 2], map: &Map) -> Option<Action> {
+    let place = |scenery| {
+        Some(Action::Edit(Edit::Place {
+            position: *user,
+            scenery,
+        }))
+    };
+
     match key {
         // Move cursor position
         Key::Left => {
-            map.wrap(user, [0, -1]);
+            map.nudge(user, [0, -1]);
+            None
         }
         Key::Right => {
-            map.wrap(user, [0, 1]);
+            map.nudge(user, [0, 1]);
+            None
         }
         Key::Up => {
-            map.wrap(user, [-1, 0]);
+            map.nudge(user, [-1, 0]);
+            None
         }
         Key::Down => {
-            map.wrap(user, [1, 0]);
+            map.nudge(user, [1, 0]);
+            None
         }
 
         // Insert new scenery
-        Key::Char('/') => {
-            map.update(user, Scenery::ForwardWedge);
-        }
-        Key::Char('\\') => {
-            map.update(user, Scenery::BackwardWedge);
-        }
-        Key::Char('#') => {
-            map.update(user, Scenery::Block);
-        }
-        Key::Char('@') => {
-            map.update(user, Scenery::Trampoline);
-        }
-        Key::Char('>') => {
-            map.update(user, Scenery::ForwardBoost);
-        }
-        Key::Char('<') => {
-            map.update(user, Scenery::BackwardBoost);
-        }
+        Key::Char('/') => place(Scenery::ForwardWedge),
+        Key::Char('\\') => place(Scenery::BackwardWedge),
+        Key::Char('#') => place(Scenery::Block),
+        Key::Char('@') => place(Scenery::Trampoline),
+        Key::Char('>') => place(Scenery::ForwardBoost),
+        Key::Char('<') => place(Scenery::BackwardBoost),
+        Key::Char('T') => place(Scenery::Teleporter),
+
+        // Take back placed scenery
+        Key::Delete | Key::Backspace => Some(Action::Edit(Edit::Remove { position: *user })),
+        Key::Char('u') => Some(Action::Edit(Edit::Undo)),
+        Key::Ctrl('r') => Some(Action::Edit(Edit::Redo)),
 
         // Quit level
-        Key::Char('q') => {
-            *complete = true;
-        }
+        Key::Char('q') => Some(Action::Quit),
         // Reset level
-        Key::Char('r') => {
-            *reset = true;
-        }
+        Key::Char('r') => Some(Action::Reset),
 
-        _ => (),
+        // Give the crab under the cursor a job
+        Key::Char(ch) => Skill::from_key(ch).map(|skill| {
+            Action::Edit(Edit::Assign {
+                position: *user,
+                skill,
+            })
+        }),
+
+        _ => None,
     }
 }
 
@@ -579,15 +1589,15 @@ pub fn check_resize(term_size: &mut (u16, u16)) -> bool {
     match termion::terminal_size() {
         Ok(new) => {
             if *term_size == new {
-                return false;
+                false
             } else {
                 *term_size = new;
-                return true;
+                true
             }
         }
         Err(err) => {
             eprintln!("Error determining terminal size: {:?}", err.kind());
-            return true;
+            true
         }
     }
 }
@@ -599,91 +1609,3 @@ pub fn prompt_for_filename() -> Result<String, std::io::Error> {
     println!("Saving map to: {}\n", buffer);
     Ok(buffer)
 }
-
-pub fn prompt_for_positions() -> Result<Vec<[usize; 2]>, std::io::Error> {
-    println!("\nPlease enter crab positions");
-    println!("First provide a list of x coordinates (between 0 and 79):\n");
-    let mut xbuffer = String::new();
-    stdin().read_line(&mut xbuffer)?;
-
-    println!("\nSecond provide a list of y coordinates (between 0 and 79):\n");
-    let mut ybuffer = String::new();
-    stdin().read_line(&mut ybuffer)?;
-
-    eprintln!("\nYou have provided the following crab positions [y,x]:");
-    let mut positions: Vec<[usize; 2]> = Vec::new();
-    for (xi, yi) in xbuffer.chars().zip(ybuffer.chars()) {
-        xi.to_digit(10).and_then(|xi| {
-            yi.to_digit(10).map(|yi| {
-                eprintln!("[{}, {}]", yi, xi);
-                positions.push([yi as usize, xi as usize])
-            })
-        });
-    }
-
-    Ok(positions)
-}
-
-pub fn prompt_for_velocities() -> Result<Vec<[isize; 2]>, std::io::Error> {
-    println!("\nPlease enter crab positions");
-    println!("First provide a list of x coordinates (between 0 and 79):\n");
-    let mut xbuffer = String::new();
-    stdin().read_line(&mut xbuffer)?;
-
-    println!("\nSecond provide a list of y coordinates (between 0 and 79):\n");
-    let mut ybuffer = String::new();
-    stdin().read_line(&mut ybuffer)?;
-
-    eprintln!("\nYou have provided the following crab positions [y,x]:");
-    let mut velocities: Vec<[isize; 2]> = Vec::new();
-    for (xi, yi) in xbuffer.chars().zip(ybuffer.chars()) {
-        // Note this breaks down for negative velocities!
-        xi.to_digit(10).and_then(|xi| {
-            yi.to_digit(10).map(|yi| {
-                eprintln!("[{}, {}]", yi, xi);
-                velocities.push([yi as isize, xi as isize])
-            })
-        });
-    }
-
-    Ok(velocities)
-}
-
-pub fn save_to_ron(
-    filename: &str,
-    map: &Map,
-    positions: Vec<[usize; 2]>,
-    velocities: Vec<[isize; 2]>,
-) -> Result<(), std::io::Error> {
-    // Open file
-    let mut file = File::create(filename.trim())?;
-
-    // Convert to appropriate format
-    let mut x = String::from("{ ");
-    let mut y = String::from("{ ");
-    let mut vx = String::from("{ ");
-    let mut vy = String::from("{ ");
-    for (i, ([yi, xi], [vyi, vxi])) in positions.iter().zip(velocities.iter()).enumerate() {
-        x.push_str(&format!("{}:{}, ", i, xi));
-        y.push_str(&format!("{}:{}, ", i, yi));
-        vx.push_str(&format!("{}:{}, ", i, vxi));
-        vy.push_str(&format!("{}:{}, ", i, vyi));
-    }
-    x.push('}');
-    y.push('}');
-    vx.push('}');
-    vy.push('}');
-
-    // Output to file
-    write!(
-        file,
-        "// Custom level\n(\n\tx: {},\n\ty: {},\n\tvx: {},\n\tvy: {},\n\tlayout: \"{}\",\n)\n",
-        x,
-        y,
-        vx,
-        vy,
-        &map.to_string()
-    )?;
-
-    Ok(())
-}
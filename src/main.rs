@@ -1,16 +1,159 @@
 use std::io::{stdout, Write};
-use std::{env, thread, time};
+use std::{env, process, thread, time};
 
-use termion::input::TermRead;
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
 use termion::raw::IntoRawMode;
-use termion::{clear, color, cursor, style};
+use termion::{clear, color, cursor, style, AsyncReader};
 
-use crabs::{check_resize, process_args, user_input, Colour, TICK_TIME};
+use crabs::cli::{self, Cli, CliError, Command, Options, EXIT_FAILURE, EXIT_SUCCESS};
+use crabs::progress::Progress;
+use crabs::replay::{Playback, Replay, Session};
+use crabs::solve::{self as solver, Verdict};
+use crabs::{check_resize, editor, Colour, Level, Origin, Outcome, Pack, Simulation, Team};
 
-fn main() -> Result<(), std::io::Error> {
+type Input = Keys<AsyncReader>;
+
+fn main() {
     // Process command line arguments
-    let mut target_tick_time: time::Duration = TICK_TIME;
-    let levels = process_args(env::args(), &mut target_tick_time);
+    let mut cli = match Cli::parse(env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => {
+            match err {
+                CliError::Help => cli::print_help(),
+                CliError::Version => println!("crabs {}", env!("CARGO_PKG_VERSION")),
+                _ => {
+                    eprintln!("crabs: {}", err);
+                    cli::print_usage();
+                }
+            }
+            process::exit(err.exit_code());
+        }
+    };
+
+    // Respect the common convention for turning colour off
+    if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        cli.options.colour = false;
+    }
+
+    let status = match cli.command {
+        Command::Play(paths) => play(&cli.options, &paths),
+        Command::Edit(filename) => match editor::run(filename, cli.options.tick_time) {
+            Ok(()) => EXIT_SUCCESS,
+            Err(err) => {
+                eprintln!("crabs: {}", err);
+                EXIT_FAILURE
+            }
+        },
+        Command::Check(paths) => {
+            if cli::check(&paths) {
+                EXIT_SUCCESS
+            } else {
+                EXIT_FAILURE
+            }
+        }
+        Command::Solve(paths) => solve(&cli.options, &paths),
+        Command::Replay(path) => watch(&path, &cli.options),
+    };
+    process::exit(status);
+}
+
+fn play(options: &Options, paths: &[String]) -> i32 {
+    // Gather the levels to play, a requested level is played straight away
+    let (packs, start) = match cli::packs(options, paths)
+        .and_then(|packs| cli::start_level(options, &packs).map(|start| (packs, start)))
+    {
+        Ok(selection) => selection,
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    match run(options, &packs, start) {
+        Ok(()) => EXIT_SUCCESS,
+        Err(err) => {
+            eprintln!("crabs: {}", err);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn solve(options: &Options, paths: &[String]) -> i32 {
+    // Solve the requested level, or every level in the packs
+    let (packs, start) = match cli::packs(options, paths)
+        .and_then(|packs| cli::start_level(options, &packs).map(|start| (packs, start)))
+    {
+        Ok(selection) => selection,
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_FAILURE;
+        }
+    };
+    let entries: Vec<(usize, usize)> = match start {
+        Some(entry) => vec![entry],
+        None => packs
+            .iter()
+            .enumerate()
+            .flat_map(|(p, pack)| (0..pack.levels.len()).map(move |l| (p, l)))
+            .collect(),
+    };
+
+    let mut status = EXIT_SUCCESS;
+    for (p, l) in entries {
+        let level = &packs[p].levels[l];
+        print!("{} ({} level {}): ", level.name, packs[p].name, l + 1);
+        stdout().flush().ok();
+        match solver::solve(level, &options.budget) {
+            Verdict::Solved(solution) => {
+                println!(
+                    "solved with {} placement(s), winning after {} ticks",
+                    solution.placements.len(),
+                    solution.ticks
+                );
+                for ([y, x], scenery) in solution.placements.iter() {
+                    println!("\tplace {} at ({}, {})", scenery.to_char(), y, x);
+                }
+                if let Some(par) = level.par {
+                    println!("\tpar is {}", par);
+                }
+                println!("{}\n", solution.map);
+            }
+            Verdict::Unsolvable => {
+                println!(
+                    "no pre-placement solution within the budget ({} placement(s), {} ticks)",
+                    options.budget.placements, options.budget.ticks
+                );
+                status = EXIT_FAILURE;
+            }
+            Verdict::SkillsUntried => {
+                println!(
+                    "no solution from placements alone, and the solver can't try the level's skills"
+                );
+                status = EXIT_FAILURE;
+            }
+            Verdict::GaveUp => {
+                println!(
+                    "gave up after {} attempts, try a larger --max-attempts",
+                    options.budget.attempts
+                );
+                status = EXIT_FAILURE;
+            }
+        }
+    }
+    status
+}
+
+fn run(
+    options: &Options,
+    packs: &[Pack],
+    mut start: Option<(usize, usize)>,
+) -> Result<(), std::io::Error> {
+    // Load saved progress (a broken save file shouldn't stop play)
+    let mut progress = Progress::load().unwrap_or_else(|err| {
+        eprintln!("Unable to load saved progress: {}", err);
+        Progress::default()
+    });
 
     // Initialise terminal
     let mut stdout = stdout().into_raw_mode()?;
@@ -21,82 +164,360 @@ fn main() -> Result<(), std::io::Error> {
     let mut term_size: (u16, u16) = (0, 0);
     check_resize(&mut term_size);
 
-    // Loop over levels
-    for level in levels.iter() {
-        // Initialise level
-        let (mut crabs, mut map) = level.clone();
-
-        // User position
-        let mut user = [map.dimensions[0] / 2, map.dimensions[1] / 2];
-
-        // Game loop
-        let mut complete = false;
-        let mut reset = false;
-        'game: while !complete {
-            let start_time = time::Instant::now();
-
-            // Check if level needs to be reset
-            if reset {
-                crabs = level.0.clone();
-                map = level.1.clone();
-                user = [map.dimensions[0] / 2, map.dimensions[1] / 2];
-                reset = false;
-            }
-
-            // Ensure that map is crab-free
-            map.decrab();
-
-            // Crabs are advanced
-            crabs.evolve(&mut map, &mut complete);
-
-            // Allow user to adjust map (input is asynchronous)
-            loop {
-                match stdin.next() {
-                    Some(res) => {
-                        res.ok().map(|key| {
-                            user_input(key, &mut user, &mut complete, &mut reset, &mut map);
-                        });
+    // Every level in every pack, in order
+    let entries: Vec<(usize, usize)> = packs
+        .iter()
+        .enumerate()
+        .flat_map(|(p, pack)| (0..pack.levels.len()).map(move |l| (p, l)))
+        .collect();
+
+    // Start from the requested level, or the first that hasn't been completed yet
+    let mut selected = entries
+        .iter()
+        .position(|&(p, l)| match start {
+            Some(entry) => entry == (p, l),
+            None => progress.is_unlocked(&packs[p], l) && !progress.is_completed(&packs[p], l),
+        })
+        .unwrap_or(0);
+
+    // Let the player choose levels until they quit
+    let mut results: Vec<(String, Outcome)> = Vec::new();
+    let mut save_error = None;
+    let mut record_error = None;
+    loop {
+        let (p, l) = match start.take() {
+            Some(entry) => entry,
+            None => match level_select(
+                &mut stdout,
+                &mut stdin,
+                packs,
+                &entries,
+                &progress,
+                &mut selected,
+            )? {
+                Some(entry) => entry,
+                None => break,
+            },
+        };
+        let level = &packs[p].levels[l];
+        let mut replay = options
+            .record
+            .as_ref()
+            .map(|_| Replay::new(&packs[p].name, l, level, options.tick_time, options.seed));
+        let simulation = play_level(
+            &mut stdout,
+            &mut stdin,
+            level,
+            options,
+            &mut term_size,
+            replay.as_mut(),
+        )?;
+
+        // Keep a recording of every level played, each in its own file
+        if let (Some(path), Some(replay)) = (&options.record, replay) {
+            if let Err(err) = replay.save(&Replay::numbered(path, results.len() + 1)) {
+                record_error = Some(err);
+            }
+        }
+
+        if simulation.outcome() == Outcome::Won {
+            progress.complete(&packs[p], l, simulation.placements(), simulation.tick());
+            if let Err(err) = progress.save() {
+                save_error = Some(err);
+            }
+
+            // Move on to the next level
+            if selected + 1 < entries.len() {
+                selected += 1;
+            }
+        }
+        results.push((level.name.clone(), simulation.outcome()));
+    }
+
+    // Reset stdout
+    write!(
+        stdout,
+        "{}{}{}{}",
+        clear::All,
+        style::Reset,
+        cursor::Goto(1, 1),
+        cursor::Show
+    )?;
+
+    // Report how each level went
+    drop(stdout);
+    for (name, outcome) in results.iter() {
+        let result = match outcome {
+            Outcome::Won => "complete",
+            Outcome::Lost => "failed",
+            Outcome::Quit => "skipped",
+            Outcome::Playing => "unfinished",
+        };
+        println!("{}: {}", name, result);
+    }
+    if let Some(err) = save_error {
+        eprintln!("Unable to save progress: {}", err);
+    }
+    if let Some(err) = record_error {
+        eprintln!("Unable to save replay: {}", err);
+    }
+
+    Ok(())
+}
+
+fn level_select<W: Write>(
+    stdout: &mut W,
+    stdin: &mut Input,
+    packs: &[Pack],
+    entries: &[(usize, usize)],
+    progress: &Progress,
+    selected: &mut usize,
+) -> Result<Option<(usize, usize)>, std::io::Error> {
+    write!(stdout, "{}", clear::All)?;
+    let mut redraw = true;
+    loop {
+        if redraw {
+            // Lay out a heading for each pack followed by its levels
+            let mut lines: Vec<(Option<usize>, String)> = vec![
+                (
+                    None,
+                    String::from("Choose a level (arrow keys, Enter to play, q to quit)"),
+                ),
+                (None, String::new()),
+            ];
+            for (index, &(p, l)) in entries.iter().enumerate() {
+                let pack = &packs[p];
+                if l == 0 {
+                    lines.push((None, pack.name.clone()));
+                }
+                let status = if !progress.is_unlocked(pack, l) {
+                    String::from("locked")
+                } else {
+                    match progress.record(pack, l) {
+                        Some(record) if record.completed => format!(
+                            "complete  best: {} placed, {} ticks",
+                            record.best_placements.unwrap_or(0),
+                            record.best_ticks.unwrap_or(0)
+                        ),
+                        _ => String::new(),
                     }
-                    None => { break; }
+                };
+                lines.push((
+                    Some(index),
+                    format!("{:>4}. {:<30} {}", l + 1, pack.levels[l].name, status),
+                ));
+            }
+
+            // Scroll so the selected level stays on screen
+            let height = termion::terminal_size()
+                .map_or(24, |(_, h)| h as usize)
+                .max(3);
+            let row = lines
+                .iter()
+                .position(|(index, _)| *index == Some(*selected))
+                .unwrap_or(0);
+            let offset = (row + 1).saturating_sub(height);
+
+            write!(stdout, "{}", clear::All)?;
+            for (y, (index, line)) in lines.iter().skip(offset).take(height).enumerate() {
+                let marker = if *index == Some(*selected) { '>' } else { ' ' };
+                write!(
+                    stdout,
+                    "{}{} {}",
+                    cursor::Goto(1, y as u16 + 1),
+                    marker,
+                    line
+                )?;
+            }
+            stdout.flush()?;
+            redraw = false;
+        }
+
+        match stdin.next() {
+            Some(Ok(Key::Up)) if *selected > 0 => {
+                *selected -= 1;
+                redraw = true;
+            }
+            Some(Ok(Key::Down)) if *selected + 1 < entries.len() => {
+                *selected += 1;
+                redraw = true;
+            }
+            Some(Ok(Key::Char('\n'))) => {
+                let (p, l) = entries[*selected];
+                if progress.is_unlocked(&packs[p], l) {
+                    write!(stdout, "{}", clear::All)?;
+                    return Ok(Some((p, l)));
                 }
             }
+            Some(Ok(Key::Char('q'))) | Some(Ok(Key::Esc)) => return Ok(None),
+            Some(_) => (),
+            None => thread::sleep(time::Duration::from_millis(10)),
+        }
+    }
+}
 
-            // Check if terminal has been resized
-            if check_resize(&mut term_size) {
-                // Clear before redraw
-                write!(stdout, "{}", clear::All)?;
+fn play_level<W: Write>(
+    stdout: &mut W,
+    stdin: &mut Input,
+    level: &Level,
+    options: &Options,
+    term_size: &mut (u16, u16),
+    mut replay: Option<&mut Replay>,
+) -> Result<Simulation, std::io::Error> {
+    // Initialise level
+    let mut session = Session::new(level.clone(), options.seed);
+
+    // Game loop
+    while session.simulation().outcome() == Outcome::Playing {
+        let start_time = time::Instant::now();
+
+        // Crabs are advanced
+        session.step();
+
+        // Allow user to adjust map (input is asynchronous)
+        for key in stdin.by_ref().flatten() {
+            if let Some(replay) = replay.as_deref_mut() {
+                replay.record(session.simulation().tick(), key);
             }
+            session.press(key);
+        }
 
-            // Display current state to stdout
-            for (y, x, ch) in map.clone() {
-                if y == user[0] && x == user[1] {
-                    // Position cursor for user
-                    write!(
-                        stdout,
-                        "{}{}{}{}",
-                        cursor::Goto(user[1] as u16 + 1, user[0] as u16 + 1),
-                        color::Fg(color::Green),
-                        '+',
-                        color::Fg(color::Reset)
-                    )?;
-                } else {
-                    // Display map
-                    write!(
-                        stdout,
-                        "{}{}{}{}",
-                        cursor::Goto(x as u16 + 1, y as u16 + 1),
-                        ch.to_fg_colour(),
-                        ch,
-                        color::Fg(color::Reset)
-                    )?;
+        // Check if terminal has been resized
+        if check_resize(term_size) {
+            // Clear before redraw
+            write!(stdout, "{}", clear::All)?;
+        }
+
+        // Display current state to stdout
+        draw(stdout, &session, options)?;
+
+        // Offer another attempt when the level is lost
+        if session.simulation().outcome() == Outcome::Lost {
+            write!(
+                stdout,
+                "{}{}Too many crabs lost! Type r to restart or q to quit.",
+                cursor::Goto(1, session.simulation().map().dimensions[0] as u16 + 3),
+                clear::CurrentLine
+            )?;
+            stdout.flush()?;
+            if wait_for_retry(stdin) {
+                if let Some(replay) = replay.as_deref_mut() {
+                    replay.record(session.simulation().tick(), Key::Char('r'));
                 }
+                session.restart();
+                write!(stdout, "{}", clear::All)?;
             }
+        }
+
+        // Allow main thread to sleep until required for next tick
+        let tick_time = time::Instant::now() - start_time;
+        if tick_time < options.tick_time {
+            thread::sleep(options.tick_time - tick_time);
+        }
+    }
+    write!(stdout, "{}", clear::All)?;
+
+    Ok(session.simulation().clone())
+}
+
+fn watch(path: &str, options: &Options) -> i32 {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return EXIT_FAILURE;
+        }
+    };
 
-            // Allow main thread to sleep until required for next tick
-            let tick_time = time::Instant::now() - start_time;
-            if tick_time < target_tick_time {
-                thread::sleep(target_tick_time - tick_time);
+    match play_back(&replay, options) {
+        Ok(simulation) => {
+            let result = match simulation.outcome() {
+                Outcome::Won => "complete",
+                Outcome::Lost => "failed",
+                Outcome::Quit => "skipped",
+                Outcome::Playing => "unfinished",
+            };
+            println!(
+                "{} ({} level {}): {} after {} ticks",
+                replay.name,
+                replay.pack,
+                replay.level,
+                result,
+                simulation.tick()
+            );
+            for ([y, x], scenery) in simulation.map().placed() {
+                println!("\tplaced {} at ({}, {})", scenery.to_char(), y, x);
             }
+            EXIT_SUCCESS
+        }
+        Err(err) => {
+            eprintln!("crabs: {}", err);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn play_back(replay: &Replay, options: &Options) -> Result<Simulation, std::io::Error> {
+    // Initialise terminal
+    let mut stdout = stdout().into_raw_mode()?;
+    let mut stdin = termion::async_stdin().keys();
+    write!(stdout, "{}{}", clear::All, cursor::Hide)?;
+    let mut term_size: (u16, u16) = (0, 0);
+    check_resize(&mut term_size);
+
+    let mut playback = Playback::new(replay);
+    let tick_time = time::Duration::from_millis(replay.tick_time);
+    let row = playback.session().simulation().map().dimensions[0] as u16 + 3;
+    let mut paused = false;
+    let mut fast = false;
+    let mut quit = false;
+
+    while !quit {
+        let start_time = time::Instant::now();
+
+        // Viewer controls
+        let mut step = !paused && !playback.is_finished();
+        for key in stdin.by_ref().flatten() {
+            match key {
+                Key::Char(' ') => paused = !paused,
+                Key::Char('n') if paused && !playback.is_finished() => step = true,
+                Key::Char('f') => fast = !fast,
+                Key::Char('q') | Key::Esc => quit = true,
+                _ => (),
+            }
+        }
+        if step {
+            playback.step();
+        }
+
+        if check_resize(&mut term_size) {
+            write!(stdout, "{}", clear::All)?;
+        }
+        draw(&mut stdout, playback.session(), options)?;
+        let state = if playback.is_finished() {
+            "finished"
+        } else if paused {
+            "paused"
+        } else if fast {
+            "fast-forward"
+        } else {
+            "playing"
+        };
+        write!(
+            stdout,
+            "{}{}Replay tick {} ({})  space pause, n step, f fast-forward, q quit",
+            cursor::Goto(1, row),
+            clear::CurrentLine,
+            playback.session().simulation().tick(),
+            state
+        )?;
+        stdout.flush()?;
+
+        // Fast-forward runs at eight times the recorded speed
+        let target = if fast { tick_time / 8 } else { tick_time };
+        let elapsed = time::Instant::now() - start_time;
+        if elapsed < target {
+            thread::sleep(target - elapsed);
         }
     }
 
@@ -109,7 +530,143 @@ fn main() -> Result<(), std::io::Error> {
         cursor::Goto(1, 1),
         cursor::Show
     )?;
+    Ok(playback.session().simulation().clone())
+}
 
-    // Successful return
-    Ok(())
+fn draw<W: Write>(
+    stdout: &mut W,
+    session: &Session,
+    options: &Options,
+) -> Result<(), std::io::Error> {
+    let simulation = session.simulation();
+    let user = session.cursor();
+
+    for (y, x, ch) in simulation.map().clone() {
+        if y == user[0] && x == user[1] {
+            // Position cursor for user
+            let green = if options.colour {
+                format!("{}", color::Fg(color::Green))
+            } else {
+                String::new()
+            };
+            write!(
+                stdout,
+                "{}{}+{}",
+                cursor::Goto(user[1] as u16 + 1, user[0] as u16 + 1),
+                green,
+                paint(' ', options)
+            )?;
+        } else {
+            // Display map, with exits in the colour of the team they take and the player's own
+            // scenery underlined (even without colour, so it can still be told apart)
+            let team = simulation.map().exit([y, x]).and_then(|exit| exit.team);
+            let (mark, unmark) = if simulation.map().origin([y, x]) == Origin::Player {
+                (
+                    format!("{}", style::Underline),
+                    format!("{}", style::NoUnderline),
+                )
+            } else {
+                (String::new(), String::new())
+            };
+            write!(
+                stdout,
+                "{}{}{}{}{}{}",
+                cursor::Goto(x as u16 + 1, y as u16 + 1),
+                tint(ch, team, options),
+                mark,
+                ch,
+                unmark,
+                paint(' ', options)
+            )?;
+        }
+    }
+
+    // Crabs crossing speed pads aren't part of the map, so draw every crab on top (in the
+    // glyph for its kind and the colour of its team)
+    for crab in simulation.entities().iter() {
+        let [y, x] = crab.position();
+        if [y, x] != user {
+            let glyph = crab.kind().to_char();
+            write!(
+                stdout,
+                "{}{}{}{}",
+                cursor::Goto(x as u16 + 1, y as u16 + 1),
+                tint(glyph, crab.team(), options),
+                glyph,
+                paint(' ', options)
+            )?;
+        }
+    }
+
+    // Display rescue progress and remaining inventory below the map
+    write!(
+        stdout,
+        "{}{}{}  Released: {}  Saved: {}/{}  Remaining: {}  Lost: {}  Placed: {}",
+        cursor::Goto(1, simulation.map().dimensions[0] as u16 + 2),
+        clear::CurrentLine,
+        session.level().name,
+        simulation.released(),
+        simulation.rescued(),
+        simulation.quota(),
+        simulation.remaining(),
+        simulation.lost(),
+        simulation.placements()
+    )?;
+    for (scenery, count) in simulation.map().inventory.counts() {
+        let ch = scenery.to_char();
+        write!(
+            stdout,
+            "  {}{}{} x{}",
+            paint(ch, options),
+            ch,
+            paint(' ', options),
+            count
+        )?;
+    }
+    for (skill, count) in simulation.skills().counts() {
+        write!(stdout, "  {} {} x{}", skill.key(), skill.name(), count)?;
+    }
+    for exit in simulation.map().exits.iter() {
+        let capacity = exit
+            .capacity
+            .map_or_else(|| String::from("-"), |capacity| capacity.to_string());
+        write!(
+            stdout,
+            "  {}{}{} {}/{}",
+            tint('X', exit.team, options),
+            exit.name,
+            paint(' ', options),
+            exit.used().len(),
+            capacity
+        )?;
+    }
+    stdout.flush()
+}
+
+fn wait_for_retry(stdin: &mut Input) -> bool {
+    // Block until the player decides whether to try again
+    loop {
+        match stdin.next() {
+            Some(Ok(Key::Char('r'))) => return true,
+            Some(Ok(Key::Char('q'))) => return false,
+            _ => thread::sleep(time::Duration::from_millis(10)),
+        }
+    }
+}
+
+// Foreground colour for a glyph (a blank resets it), unless colour has been turned off
+fn paint(ch: char, options: &Options) -> String {
+    if options.colour {
+        ch.to_fg_colour()
+    } else {
+        String::new()
+    }
+}
+
+// Like `paint`, but in the team's colour if there is one
+fn tint(ch: char, team: Option<Team>, options: &Options) -> String {
+    match team {
+        Some(team) if options.colour => team.to_fg_colour(),
+        _ => paint(ch, options),
+    }
 }
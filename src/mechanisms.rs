@@ -0,0 +1,65 @@
+use crate::{Cause, Crab, Fate, Map, Scenery};
+
+/// Pressure plate and the doors it switches
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Link {
+    /// Position [y, x] of the plate
+    pub plate: [usize; 2],
+    /// Positions [y, x] of the doors
+    pub doors: Vec<[usize; 2]>,
+}
+
+impl Map {
+    /// Whether a crab is standing on the plate at this cell
+    pub fn is_pressed(&self, plate: [usize; 2]) -> bool {
+        self.neighbour(plate, [-1, 0])
+            .is_ok_and(|above| self.crabs.contains(&above))
+    }
+
+    // Hold each linked door the other way while any of its plates are pressed, unless there's a
+    // crab in the doorway
+    pub(crate) fn switch(&mut self) {
+        let mut doors: Vec<([usize; 2], bool)> = Vec::new();
+        for link in self.links.iter() {
+            let pressed = self.is_pressed(link.plate);
+            for &door in link.doors.iter() {
+                match doors.iter_mut().find(|(cell, _)| *cell == door) {
+                    Some((_, toggled)) => *toggled |= pressed,
+                    None => doors.push((door, pressed)),
+                }
+            }
+        }
+
+        for (cell, toggled) in doors {
+            if let Scenery::Door { open, .. } = self.layout[cell[0]][cell[1]] {
+                if !self.crabs.contains(&cell) {
+                    self.overide(&cell, Scenery::Door { open, toggled });
+                }
+            }
+        }
+    }
+}
+
+impl Crab {
+    /// Conveyor belts carry a crab standing on one a cell along, before it moves itself
+    pub(crate) fn ride(&mut self, map: &mut Map) -> Fate {
+        let belt = match map.ahead(self.position, [1, 0]) {
+            Ok((_, belt @ Scenery::Conveyor { .. })) => belt,
+            _ => return Fate::Alive,
+        };
+        match map.ahead(self.position, [0, belt.heading()]) {
+            Ok((next, scenery))
+                if next != self.position && (scenery == Scenery::Empty || scenery.is_boost()) =>
+            {
+                self.enter(map, next);
+                Fate::Alive
+            }
+            Ok((next, Scenery::Safety)) => Fate::Rescued(next),
+            Ok((_, hazard @ (Scenery::Spikes | Scenery::Water | Scenery::Lava))) => {
+                Fate::Died(Cause::Hazard(hazard))
+            }
+            Ok(_) => Fate::Alive,
+            Err(cause) => Fate::Died(cause),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ron::de::from_str;
+use serde::Deserialize;
+
+use crate::format::{load_level, LevelError};
+use crate::{levels, Level};
+
+/// Name of the manifest looked for when a pack is given as a directory
+pub const MANIFEST: &str = "pack.ron";
+
+/// Pack manifest, listing level files (relative to the manifest) in the order they are played
+///
+/// ```text
+/// (
+///     name: "Seaside",
+///     levels: [
+///         (title: "Rock pools", file: "rock_pools.ron"),
+///         (title: "High tide", file: "high_tide.ron"),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct PackFile {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    pub levels: Vec<PackEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PackEntry {
+    pub title: String,
+    pub file: String,
+}
+
+/// An ordered collection of levels, played one after the other
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub name: String,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug)]
+pub enum PackError {
+    Io(PathBuf, std::io::Error),
+    /// Manifest couldn't be decoded
+    Manifest(PathBuf, ron::de::Error),
+    /// One of the levels in the pack couldn't be loaded
+    Level(PathBuf, LevelError),
+    /// Directory or manifest doesn't contain any level files
+    Empty(PathBuf),
+    /// Two levels share a title, so progress on them couldn't be told apart
+    DuplicateTitle(PathBuf, String),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            PackError::Manifest(path, err) => {
+                write!(
+                    f,
+                    "{}: unable to parse pack manifest: {}",
+                    path.display(),
+                    err
+                )
+            }
+            PackError::Level(path, err) => write!(f, "{}: {}", path.display(), err),
+            PackError::Empty(path) => write!(f, "{}: no level files found", path.display()),
+            PackError::DuplicateTitle(path, title) => write!(
+                f,
+                "{}: more than one level is titled {:?}",
+                path.display(),
+                title
+            ),
+        }
+    }
+}
+
+impl Pack {
+    /// Levels built into the game
+    pub fn default_pack() -> Self {
+        Pack {
+            name: String::from("Crabs"),
+            levels: levels::default_levels(),
+        }
+    }
+
+    /// Load a pack from a manifest, a directory of level files or a single level file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PackError> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let manifest = path.join(MANIFEST);
+            if manifest.is_file() {
+                Pack::from_manifest(&manifest)
+            } else {
+                Pack::from_directory(path)
+            }
+        } else {
+            let source =
+                fs::read_to_string(path).map_err(|err| PackError::Io(path.to_path_buf(), err))?;
+            match from_str::<PackFile>(&source) {
+                Ok(manifest) => Pack::from_pack_file(path, manifest),
+                Err(_) => {
+                    // Not a manifest, so treat it as a pack of one level
+                    let mut level = load(path)?;
+                    level.name = title(path, &level);
+                    Ok(Pack {
+                        name: level.name.clone(),
+                        levels: vec![level],
+                    })
+                }
+            }
+        }
+    }
+
+    fn from_manifest(path: &Path) -> Result<Self, PackError> {
+        let source =
+            fs::read_to_string(path).map_err(|err| PackError::Io(path.to_path_buf(), err))?;
+        let manifest: PackFile =
+            from_str(&source).map_err(|err| PackError::Manifest(path.to_path_buf(), err))?;
+        Pack::from_pack_file(path, manifest)
+    }
+
+    fn from_pack_file(path: &Path, manifest: PackFile) -> Result<Self, PackError> {
+        // Level files are found relative to the manifest
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        if manifest.levels.is_empty() {
+            return Err(PackError::Empty(path.to_path_buf()));
+        }
+        let mut levels: Vec<Level> = Vec::new();
+        for entry in manifest.levels {
+            let mut level = load(&directory.join(&entry.file))?;
+            level.name = entry.title;
+            levels.push(level);
+        }
+        check_titles(path, &levels)?;
+        Ok(Pack {
+            name: manifest.name,
+            levels,
+        })
+    }
+
+    fn from_directory(path: &Path) -> Result<Self, PackError> {
+        // Levels are played in order of file name
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .map_err(|err| PackError::Io(path.to_path_buf(), err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        files.sort();
+        if files.is_empty() {
+            return Err(PackError::Empty(path.to_path_buf()));
+        }
+
+        let mut levels: Vec<Level> = Vec::new();
+        for file in files {
+            let mut level = load(&file)?;
+            level.name = title(&file, &level);
+            levels.push(level);
+        }
+        check_titles(path, &levels)?;
+        Ok(Pack {
+            name: stem(path),
+            levels,
+        })
+    }
+}
+
+fn load(path: &Path) -> Result<Level, PackError> {
+    load_level(&path.to_string_lossy()).map_err(|err| PackError::Level(path.to_path_buf(), err))
+}
+
+// Progress is kept by title, so each level in a pack needs its own
+fn check_titles(path: &Path, levels: &[Level]) -> Result<(), PackError> {
+    for (i, level) in levels.iter().enumerate() {
+        if levels[..i].iter().any(|other| other.name == level.name) {
+            return Err(PackError::DuplicateTitle(
+                path.to_path_buf(),
+                level.name.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Untitled levels are named after their file
+fn title(path: &Path, level: &Level) -> String {
+    if level.name.is_empty() {
+        stem(path)
+    } else {
+        level.name.clone()
+    }
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use ron::de::from_str;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+
+use crate::pack::Pack;
+
+/// Name of the save file within the data directory
+pub const SAVE_FILE: &str = "progress.ron";
+
+/// Directory of installed level packs within the data directory
+pub const PACKS: &str = "packs";
+
+/// Best results for a single level
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub completed: bool,
+    pub unlocked: bool,
+    /// Fewest pieces of scenery placed in a winning attempt
+    pub best_placements: Option<usize>,
+    /// Fewest ticks taken by a winning attempt
+    pub best_ticks: Option<usize>,
+}
+
+/// Everything remembered between runs of the game
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    // Records are keyed by pack name then level name, which packs keep unique
+    levels: BTreeMap<String, Record>,
+}
+
+impl Progress {
+    /// Read progress from the save file, starting afresh if there isn't one yet
+    pub fn load() -> Result<Self, io::Error> {
+        match data_dir() {
+            Some(dir) => match fs::read_to_string(dir.join(SAVE_FILE)) {
+                Ok(source) => {
+                    from_str(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(Progress::default()),
+                Err(err) => Err(err),
+            },
+            None => Ok(Progress::default()),
+        }
+    }
+
+    /// Write progress to the save file
+    pub fn save(&self) -> Result<(), io::Error> {
+        let dir = data_dir().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no home or data directory set")
+        })?;
+        fs::create_dir_all(&dir)?;
+        let source = to_string_pretty(self, PrettyConfig::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(dir.join(SAVE_FILE), source)
+    }
+
+    pub fn record(&self, pack: &Pack, index: usize) -> Option<&Record> {
+        self.levels.get(&key(pack, index))
+    }
+
+    /// The first level of a pack is always open, the rest once the previous level is complete
+    pub fn is_unlocked(&self, pack: &Pack, index: usize) -> bool {
+        index == 0
+            || self
+                .record(pack, index)
+                .is_some_and(|record| record.unlocked)
+    }
+
+    pub fn is_completed(&self, pack: &Pack, index: usize) -> bool {
+        self.record(pack, index)
+            .is_some_and(|record| record.completed)
+    }
+
+    /// Remember a winning attempt, keeping the best scores and unlocking the next level
+    pub fn complete(&mut self, pack: &Pack, index: usize, placements: usize, ticks: usize) {
+        let record = self.levels.entry(key(pack, index)).or_default();
+        record.completed = true;
+        record.unlocked = true;
+        record.best_placements = Some(
+            record
+                .best_placements
+                .map_or(placements, |best| best.min(placements)),
+        );
+        record.best_ticks = Some(record.best_ticks.map_or(ticks, |best| best.min(ticks)));
+
+        if index + 1 < pack.levels.len() {
+            self.levels
+                .entry(key(pack, index + 1))
+                .or_default()
+                .unlocked = true;
+        }
+    }
+}
+
+fn key(pack: &Pack, index: usize) -> String {
+    format!("{}/{}", pack.name, pack.levels[index].name)
+}
+
+/// Where progress and installed packs live: $XDG_DATA_HOME/crabs (or ~/.local/share/crabs)
+pub fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("crabs"))
+}
+
+/// Packs installed in the data directory (each a directory or pack manifest), by file name
+pub fn installed_packs() -> Vec<Result<Pack, crate::pack::PackError>> {
+    let mut paths: Vec<PathBuf> = data_dir()
+        .and_then(|dir| fs::read_dir(dir.join(PACKS)).ok())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths.into_iter().map(Pack::load).collect()
+}
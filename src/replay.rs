@@ -0,0 +1,310 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time;
+
+use ron::de::from_str;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use termion::event::Key;
+
+use crate::format::LevelFile;
+use crate::validate::{validate_level, Diagnostic};
+use crate::{user_input, Action, Event, Level, Outcome, Simulation};
+
+/// Current version of the replay file format
+pub const VERSION: u32 = 1;
+
+/// A key press the game responds to, in a form that can be saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Keystroke {
+    Left,
+    Right,
+    Up,
+    Down,
+    Delete,
+    Char(char),
+    Ctrl(char),
+}
+
+impl Keystroke {
+    pub fn from_key(key: Key) -> Option<Self> {
+        match key {
+            Key::Left => Some(Keystroke::Left),
+            Key::Right => Some(Keystroke::Right),
+            Key::Up => Some(Keystroke::Up),
+            Key::Down => Some(Keystroke::Down),
+            Key::Delete | Key::Backspace => Some(Keystroke::Delete),
+            Key::Char(ch) => Some(Keystroke::Char(ch)),
+            Key::Ctrl(ch) => Some(Keystroke::Ctrl(ch)),
+            _ => None,
+        }
+    }
+
+    pub fn to_key(self) -> Key {
+        match self {
+            Keystroke::Left => Key::Left,
+            Keystroke::Right => Key::Right,
+            Keystroke::Up => Key::Up,
+            Keystroke::Down => Key::Down,
+            Keystroke::Delete => Key::Delete,
+            Keystroke::Char(ch) => Key::Char(ch),
+            Keystroke::Ctrl(ch) => Key::Ctrl(ch),
+        }
+    }
+}
+
+/// A key pressed once the simulation had reached `tick`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    pub tick: usize,
+    pub key: Keystroke,
+}
+
+/// Recording of a level being played, enough to reproduce the run exactly
+///
+/// ```text
+/// (
+///     version: 1,
+///     pack: "Crabs",
+///     level: 1,
+///     name: "Level 1",
+///     tick_time: 100,
+///     seed: None,
+///     map: (version: 2, crabs: [...], layout: "..."),
+///     inputs: [(tick: 4, key: Right), (tick: 9, key: Char('/'))],
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// Name of the pack the level came from
+    pub pack: String,
+    /// Position of the level in its pack, counted from 1
+    pub level: usize,
+    pub name: String,
+    /// Milliseconds between ticks
+    pub tick_time: u64,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Copy of the level as it was played, so the recording still works if the pack changes
+    pub map: LevelFile,
+    pub inputs: Vec<Input>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse(ron::de::Error),
+    /// File claims a version of the format this build doesn't understand
+    Version(u32),
+    /// The recorded level can't be played
+    Invalid(Vec<Diagnostic>),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "unable to read replay file: {}", err),
+            ReplayError::Parse(err) => write!(f, "unable to parse replay file: {}", err),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay file version {} is not supported (expected {} or earlier)",
+                version, VERSION
+            ),
+            ReplayError::Invalid(diagnostics) => {
+                write!(f, "recorded level has {} problem(s)", diagnostics.len())?;
+                for diagnostic in diagnostics.iter() {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<ron::de::Error> for ReplayError {
+    fn from(err: ron::de::Error) -> Self {
+        ReplayError::Parse(err)
+    }
+}
+
+impl Replay {
+    /// Start an empty recording of the `index`th level of a pack
+    pub fn new(
+        pack: &str,
+        index: usize,
+        level: &Level,
+        tick_time: time::Duration,
+        seed: Option<u64>,
+    ) -> Self {
+        Replay {
+            version: VERSION,
+            pack: pack.to_string(),
+            level: index + 1,
+            name: level.name.clone(),
+            tick_time: tick_time.as_millis() as u64,
+            seed,
+            map: LevelFile::from_level(level),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Note a key press, ignoring keys the game doesn't respond to
+    pub fn record(&mut self, tick: usize, key: Key) {
+        if let Some(key) = Keystroke::from_key(key) {
+            self.inputs.push(Input { tick, key });
+        }
+    }
+
+    /// The level as it was when recorded
+    pub fn level(&self) -> Level {
+        self.map.clone().into_level()
+    }
+
+    pub fn load(path: &str) -> Result<Self, ReplayError> {
+        let replay: Replay = from_str(&fs::read_to_string(path)?)?;
+        if replay.version > VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        // The level is checked as if it had been saved on its own
+        let diagnostics = validate_level(&replay.map.to_ron());
+        if !diagnostics.is_empty() {
+            return Err(ReplayError::Invalid(diagnostics));
+        }
+        Ok(replay)
+    }
+
+    /// File to record the `count`th level played in a session to (counting from 1): the first
+    /// goes to `path` itself, and later ones have their count added, e.g. `run-2.ron`
+    pub fn numbered(path: &str, count: usize) -> String {
+        if count <= 1 {
+            return path.to_string();
+        }
+        let file = Path::new(path);
+        let stem = file
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let name = match file.extension() {
+            Some(extension) => format!("{}-{}.{}", stem, count, extension.to_string_lossy()),
+            None => format!("{}-{}", stem, count),
+        };
+        file.with_file_name(name).to_string_lossy().into_owned()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), io::Error> {
+        let source = to_string_pretty(self, PrettyConfig::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, source)
+    }
+}
+
+/// A level being played: the simulation along with the player's cursor
+#[derive(Debug, Clone)]
+pub struct Session {
+    level: Level,
+    seed: Option<u64>,
+    simulation: Simulation,
+    cursor: [usize; 2],
+}
+
+impl Session {
+    pub fn new(level: Level, seed: Option<u64>) -> Self {
+        let simulation = match seed {
+            Some(seed) => Simulation::with_seed(level.clone(), seed),
+            None => Simulation::new(level.clone()),
+        };
+        let dimensions = simulation.map().dimensions;
+        Session {
+            level,
+            seed,
+            simulation,
+            cursor: [dimensions[0] / 2, dimensions[1] / 2],
+        }
+    }
+
+    /// Restart the level from scratch
+    pub fn restart(&mut self) {
+        *self = Session::new(self.level.clone(), self.seed);
+    }
+
+    pub fn step(&mut self) -> Vec<Event> {
+        self.simulation.step()
+    }
+
+    /// Respond to a key press, moving the cursor or changing the level
+    pub fn press(&mut self, key: Key) -> Option<Event> {
+        match user_input(key, &mut self.cursor, self.simulation.map()) {
+            Some(Action::Edit(edit)) => Some(self.simulation.apply_edit(edit)),
+            Some(Action::Reset) => {
+                self.restart();
+                None
+            }
+            Some(Action::Quit) => {
+                self.simulation.quit();
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn simulation(&self) -> &Simulation {
+        &self.simulation
+    }
+
+    pub fn cursor(&self) -> [usize; 2] {
+        self.cursor
+    }
+}
+
+/// Plays a recording back, one tick at a time
+#[derive(Debug, Clone)]
+pub struct Playback {
+    session: Session,
+    inputs: Vec<Input>,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: &Replay) -> Self {
+        Playback {
+            session: Session::new(replay.level(), replay.seed),
+            inputs: replay.inputs.clone(),
+            next: 0,
+        }
+    }
+
+    /// Advance the level, then press the keys that were pressed at that point of the recording
+    pub fn step(&mut self) -> Vec<Event> {
+        let mut events = self.session.step();
+        // Keys pressed after a restart are recorded against the new simulation's ticks
+        while let Some(input) = self.inputs.get(self.next) {
+            if input.tick > self.session.simulation().tick() {
+                break;
+            }
+            events.extend(self.session.press(input.key.to_key()));
+            self.next += 1;
+        }
+        events
+    }
+
+    /// Recording has played out: the level is over and no key press will restart it
+    pub fn is_finished(&self) -> bool {
+        self.session.simulation().outcome() != Outcome::Playing
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
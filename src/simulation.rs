@@ -0,0 +1,357 @@
+use crate::{Cause, Entities, Level, Map, Refusal, Scenery, Skill, Skills};
+
+/// A change to the map requested by the player
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Place {
+        position: [usize; 2],
+        scenery: Scenery,
+    },
+    /// Take away scenery the player placed at `position`
+    Remove { position: [usize; 2] },
+    /// Give the crab at `position` a job
+    Assign { position: [usize; 2], skill: Skill },
+    /// Take back the last placement or removal
+    Undo,
+    /// Put back the last placement or removal taken back
+    Redo,
+}
+
+/// Something that happened whilst stepping the simulation or applying an edit
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A crab (identified by its index in the level, counting on from there for released crabs)
+    /// came out of a hatch
+    Released { crab: usize },
+    /// A crab made it to safety, through the named exit if there was one
+    Rescued { crab: usize, exit: Option<String> },
+    /// A crab was killed
+    Died { crab: usize, cause: Cause },
+    /// A crab was found overlapping scenery or another crab, and has been skipped
+    Embedded { crab: usize },
+    /// New scenery was added to the map
+    Placed {
+        position: [usize; 2],
+        scenery: Scenery,
+    },
+    /// Scenery the player placed was taken away
+    Removed {
+        position: [usize; 2],
+        scenery: Scenery,
+    },
+    /// Scenery couldn't be added or taken away
+    Rejected {
+        position: [usize; 2],
+        scenery: Scenery,
+        reason: Refusal,
+    },
+    /// A crab was given a job
+    Assigned { crab: usize, skill: Skill },
+    /// A job couldn't be given out
+    Declined {
+        position: [usize; 2],
+        skill: Skill,
+        reason: Refusal,
+    },
+    /// There was nothing to undo or redo
+    Unchanged,
+    /// The level has been won, lost or abandoned
+    Finished(Outcome),
+}
+
+/// Current state of play
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Playing,
+    /// Enough crabs made it to safety
+    Won,
+    /// Too many crabs died for the rescue quota to be met
+    Lost,
+    /// The player gave up on the level
+    Quit,
+}
+
+/// Headless game engine: a level's map and crabs, advanced one tick at a time.
+///
+/// Nothing in here reads from or writes to the terminal, so the same level can be
+/// played interactively, driven by a script or checked from a test.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    entities: Entities,
+    map: Map,
+    tick: usize,
+    outcome: Outcome,
+    quota: usize,
+    rescued: usize,
+    lost: usize,
+    placements: usize,
+    // Placements and removals that can be undone, oldest first, then those that can be redone
+    history: Vec<Event>,
+    undone: Vec<Event>,
+    rng: Option<Rng>,
+    skills: Skills,
+}
+
+impl Simulation {
+    pub fn new(level: Level) -> Self {
+        let mut simulation = Simulation {
+            entities: level.entities,
+            map: level.map,
+            tick: 0,
+            outcome: Outcome::Playing,
+            quota: level.rescue,
+            rescued: 0,
+            lost: 0,
+            placements: 0,
+            history: Vec::new(),
+            undone: Vec::new(),
+            rng: None,
+            skills: level.skills,
+        };
+
+        // Place crabs so the initial state can be displayed and edited around
+        simulation.map.decrab();
+        simulation.map.instantaneous(&simulation.entities);
+        simulation
+    }
+
+    /// Like `new`, but crabs are advanced in a shuffled order each tick, decided by the seed
+    pub fn with_seed(level: Level, seed: u64) -> Self {
+        let mut simulation = Simulation::new(level);
+        simulation.rng = Some(Rng::new(seed));
+        simulation
+    }
+
+    /// Advance every crab by one tick
+    pub fn step(&mut self) -> Vec<Event> {
+        if self.outcome != Outcome::Playing {
+            return Vec::new();
+        }
+
+        // Ensure that map is crab-free
+        self.map.decrab();
+
+        // Crabs are advanced
+        if let Some(rng) = self.rng.as_mut() {
+            self.entities.shuffle(rng);
+        }
+        let mut events = self.entities.evolve(&mut self.map);
+        self.tick += 1;
+
+        // Keep count of the crabs that are no longer in play
+        let mut regicide = false;
+        for event in events.iter() {
+            match event {
+                Event::Rescued { .. } => self.rescued += 1,
+                Event::Died { crab, .. } => {
+                    self.lost += 1;
+                    regicide |= self.entities.is_king(*crab);
+                }
+                _ => (),
+            }
+        }
+
+        // Level is lost as soon as the quota is out of reach (or a king is lost), won once every
+        // crab is accounted for
+        let remaining = self.entities.len() + self.entities.pending();
+        if regicide || self.rescued + remaining < self.quota {
+            self.outcome = Outcome::Lost;
+        } else if remaining == 0 {
+            self.outcome = Outcome::Won;
+        }
+        if self.outcome != Outcome::Playing {
+            events.push(Event::Finished(self.outcome));
+        }
+        events
+    }
+
+    /// Apply a change requested by the player
+    pub fn apply_edit(&mut self, edit: Edit) -> Event {
+        match edit {
+            Edit::Place { position, scenery } => {
+                let event = self.place(position, scenery);
+                self.remember(&event);
+                event
+            }
+            Edit::Remove { position } => {
+                let event = self.remove(position);
+                self.remember(&event);
+                event
+            }
+            Edit::Undo => match self.history.pop() {
+                Some(change) => {
+                    let event = match change {
+                        Event::Placed { position, .. } => self.remove(position),
+                        Event::Removed { position, scenery } => self.place(position, scenery),
+                        _ => Event::Unchanged,
+                    };
+                    // Changes that can no longer be taken back (say the crabs have dug a placed
+                    // block away) are forgotten
+                    if !matches!(event, Event::Rejected { .. }) {
+                        self.undone.push(change);
+                    }
+                    event
+                }
+                None => Event::Unchanged,
+            },
+            Edit::Redo => match self.undone.pop() {
+                Some(change) => {
+                    let event = match change {
+                        Event::Placed { position, scenery } => self.place(position, scenery),
+                        Event::Removed { position, .. } => self.remove(position),
+                        _ => Event::Unchanged,
+                    };
+                    if !matches!(event, Event::Rejected { .. }) {
+                        self.history.push(change);
+                    }
+                    event
+                }
+                None => Event::Unchanged,
+            },
+            Edit::Assign { position, skill } => match self.assign(position, skill) {
+                Ok(crab) => Event::Assigned { crab, skill },
+                Err(reason) => Event::Declined {
+                    position,
+                    skill,
+                    reason,
+                },
+            },
+        }
+    }
+
+    fn place(&mut self, position: [usize; 2], scenery: Scenery) -> Event {
+        match self.map.update(&position, scenery) {
+            Ok(()) => {
+                self.placements += 1;
+                Event::Placed { position, scenery }
+            }
+            Err(reason) => Event::Rejected {
+                position,
+                scenery,
+                reason,
+            },
+        }
+    }
+
+    fn remove(&mut self, position: [usize; 2]) -> Event {
+        match self.map.remove(&position) {
+            Ok(scenery) => {
+                self.placements = self.placements.saturating_sub(1);
+                Event::Removed { position, scenery }
+            }
+            Err(reason) => Event::Rejected {
+                position,
+                scenery: match reason {
+                    Refusal::OutOfBounds => Scenery::Empty,
+                    _ => self.map.layout[position[0]][position[1]],
+                },
+                reason,
+            },
+        }
+    }
+
+    // Note a change the player made so it can be undone, which rules out redoing anything
+    fn remember(&mut self, event: &Event) {
+        if matches!(event, Event::Placed { .. } | Event::Removed { .. }) {
+            self.history.push(event.clone());
+            self.undone.clear();
+        }
+    }
+
+    // Give the crab at `position` a job from the skill budget
+    fn assign(&mut self, position: [usize; 2], skill: Skill) -> Result<usize, Refusal> {
+        let crab = self.entities.find(position).ok_or(Refusal::NoCrab)?;
+        if self.skills.remaining(skill) == 0 {
+            return Err(Refusal::OutOfStock);
+        }
+        crab.assign(skill)?;
+        self.skills.take(skill);
+        Ok(crab.id())
+    }
+
+    /// Abandon the level
+    pub fn quit(&mut self) {
+        if self.outcome == Outcome::Playing {
+            self.outcome = Outcome::Quit;
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// Number of crabs that must be rescued to win
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    /// Number of crabs that have made it to safety
+    pub fn rescued(&self) -> usize {
+        self.rescued
+    }
+
+    /// Number of crabs let out so far, counting those there from the start
+    pub fn released(&self) -> usize {
+        self.entities.released()
+    }
+
+    /// Number of crabs still in play or yet to be released
+    pub fn remaining(&self) -> usize {
+        self.entities.len() + self.entities.pending()
+    }
+
+    /// Number of crabs that have died
+    pub fn lost(&self) -> usize {
+        self.lost
+    }
+
+    /// Jobs left to give out
+    pub fn skills(&self) -> &Skills {
+        &self.skills
+    }
+
+    /// Number of pieces of scenery the player has placed (and not taken away)
+    pub fn placements(&self) -> usize {
+        self.placements
+    }
+
+    /// Number of ticks evolved so far
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+}
+
+/// Small deterministic random number generator (xorshift64*), so seeded runs can be repeated
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // State must never be zero
+        Rng {
+            state: (seed ^ 0x9e37_79b9_7f4a_7c15).max(1),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniformly chosen number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Crab, Map, Refusal, Scenery};
+
+/// Number of ramps a builder lays before stopping
+pub const BRICKS: usize = 6;
+
+/// A job the player can give a crab
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Skill {
+    /// Stands still, turning back any crab that walks into it
+    Blocker,
+    /// Digs down through blocks until it reaches something else
+    Digger,
+    /// Lays a staircase of ramps the way it is walking
+    Builder,
+    /// Turns round on the spot
+    TurnAround,
+}
+
+impl Skill {
+    /// Skills in key order
+    pub const ALL: [Skill; 4] = [Self::Blocker, Self::Digger, Self::Builder, Self::TurnAround];
+
+    /// Key that assigns the skill to the crab under the cursor
+    pub fn key(&self) -> char {
+        match self {
+            Self::Blocker => '1',
+            Self::Digger => '2',
+            Self::Builder => '3',
+            Self::TurnAround => '4',
+        }
+    }
+
+    pub fn from_key(key: char) -> Option<Self> {
+        Self::ALL.iter().copied().find(|skill| skill.key() == key)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Blocker => "blocker",
+            Self::Digger => "digger",
+            Self::Builder => "builder",
+            Self::TurnAround => "turn",
+        }
+    }
+}
+
+/// Skills the player may hand out in a level, and how many of each are left
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Skills {
+    budget: HashMap<Skill, usize>,
+}
+
+impl Skills {
+    /// No skills at all (the default for a level)
+    pub fn none() -> Self {
+        Skills::default()
+    }
+
+    pub fn new(budget: HashMap<Skill, usize>) -> Self {
+        Skills { budget }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.budget.values().all(|&count| count == 0)
+    }
+
+    pub fn remaining(&self, skill: Skill) -> usize {
+        self.budget.get(&skill).copied().unwrap_or(0)
+    }
+
+    /// Remaining counts for each skill in the budget, in key order
+    pub fn counts(&self) -> Vec<(Skill, usize)> {
+        Skill::ALL
+            .iter()
+            .filter_map(|skill| self.budget.get(skill).map(|&count| (*skill, count)))
+            .collect()
+    }
+
+    pub(crate) fn take(&mut self, skill: Skill) -> bool {
+        match self.budget.get_mut(&skill) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What a crab has been told to do, worked on at the start of every tick.
+/// Paces are horizontal speeds in 1/SCALE of a cell per tick, restored once the job is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Job {
+    Blocking,
+    Digging {
+        pace: isize,
+    },
+    /// Builders alternate between laying a ramp and climbing it
+    Building {
+        pace: isize,
+        bricks: usize,
+        laying: bool,
+    },
+}
+
+impl Crab {
+    /// Take on a skill, unless already busy with another job
+    pub(crate) fn assign(&mut self, skill: Skill) -> Result<(), Refusal> {
+        let pace = self.speed[1];
+        self.job = match (skill, self.job) {
+            (Skill::TurnAround, job) => {
+                self.pace(-pace);
+                match job {
+                    Some(Job::Digging { pace }) => Some(Job::Digging { pace: -pace }),
+                    Some(Job::Building {
+                        pace,
+                        bricks,
+                        laying,
+                    }) => Some(Job::Building {
+                        pace: -pace,
+                        bricks,
+                        laying,
+                    }),
+                    job => job,
+                }
+            }
+            (_, Some(_)) => return Err(Refusal::Busy),
+            (Skill::Blocker, None) => Some(Job::Blocking),
+            (Skill::Digger, None) => Some(Job::Digging { pace }),
+            (Skill::Builder, None) => Some(Job::Building {
+                pace,
+                bricks: BRICKS,
+                laying: true,
+            }),
+        };
+        Ok(())
+    }
+
+    /// Crabs turned back by a blocker bounce off it rather than stopping behind it
+    pub(crate) fn is_blocking(&self) -> bool {
+        self.job == Some(Job::Blocking)
+    }
+
+    /// Carry on with the crab's job, before any crab moves this tick. Changes to the scenery
+    /// are noted rather than made, so every crab sees the map as it was.
+    pub(crate) fn work(&mut self, map: &Map) {
+        self.rework.clear();
+        match self.job {
+            None => (),
+            Some(Job::Blocking) => self.pace(0),
+            Some(Job::Digging { pace }) => match map.ahead(self.position, [1, 0]) {
+                Ok((below, Scenery::Block)) if below != self.position => {
+                    self.rework.push((below, Scenery::Block, Scenery::Empty));
+                    self.pace(0);
+                }
+                // Drop down the hole before digging on
+                Ok((_, Scenery::Empty)) => self.pace(0),
+                _ => {
+                    self.job = None;
+                    self.pace(pace);
+                }
+            },
+            Some(Job::Building {
+                pace,
+                bricks,
+                laying: true,
+            }) => {
+                let ramp = if pace > 0 {
+                    Scenery::ForwardWedge
+                } else {
+                    Scenery::BackwardWedge
+                };
+                match map.ahead(self.position, [0, pace.signum()]) {
+                    Ok((ahead, Scenery::Empty)) if pace != 0 && bricks > 0 => {
+                        self.rework.push((ahead, Scenery::Empty, ramp));
+                        self.pace(0);
+                        self.job = Some(Job::Building {
+                            pace,
+                            bricks: bricks - 1,
+                            laying: false,
+                        });
+                    }
+                    // Wait for a crab to get out of the way
+                    Ok((_, Scenery::StationaryCrab)) if pace != 0 && bricks > 0 => self.pace(0),
+                    _ => {
+                        self.job = None;
+                        self.pace(pace);
+                    }
+                }
+            }
+            Some(Job::Building { pace, bricks, .. }) => {
+                // Climb the ramp just laid
+                self.pace(pace);
+                self.job = Some(Job::Building {
+                    pace,
+                    bricks,
+                    laying: true,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+
+use crate::{Edit, Event, Level, Map, Outcome, Scenery, Simulation};
+
+/// A piece of scenery put down before the crabs set off
+pub type Placement = ([usize; 2], Scenery);
+
+/// Limits on how hard the solver looks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    /// Most pieces of scenery a solution may use
+    pub placements: usize,
+    /// Ticks a level may run for before an attempt counts as a failure
+    pub ticks: usize,
+    /// Most attempts to simulate before giving up
+    pub attempts: usize,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            placements: 3,
+            ticks: 1000,
+            attempts: 200_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub placements: Vec<Placement>,
+    /// Ticks taken to win
+    pub ticks: usize,
+    /// Starting map, crabs included, with the solution's scenery added
+    pub map: Map,
+}
+
+/// What the solver found out about a level
+#[derive(Debug, Clone)]
+pub enum Verdict {
+    /// A solution using as few placements as possible
+    Solved(Box<Solution>),
+    /// No pre-placement solution within the placement and tick budget (the level may still
+    /// be won by placing scenery while the crabs move)
+    Unsolvable,
+    /// Ran out of attempts before searching every combination
+    GaveUp,
+    /// Nothing placed beforehand wins, but the level hands out skills, which the solver
+    /// doesn't try assigning
+    SkillsUntried,
+}
+
+// Result of playing a level with some scenery added at the start
+struct Trial {
+    won: Option<usize>,
+    // Empty cells crabs moved through, where scenery would change how they move
+    trail: Vec<[usize; 2]>,
+    // Scenery still left in the inventory
+    stock: Vec<Scenery>,
+}
+
+/// Find the fewest placements, made before the crabs set off, that win the level.
+///
+/// Combinations are tried in order of size. Scenery can only change how the crabs move
+/// if it is put somewhere they go, so each combination is only extended with cells on
+/// the trails of its own attempt. This means the search is exhaustive: `Unsolvable` is
+/// only reported once every combination within the budget has been ruled out, meaning
+/// there is no pre-placement solution within the budget. Scenery placed while the crabs
+/// are moving is never tried, so a player may still find a way to win. (The one
+/// exception is a teleporter, whose second pad is only tried where the crabs have been.)
+/// Skills are never handed out, so levels with skills that can't be won without them are
+/// reported as `SkillsUntried` rather than unsolvable.
+pub fn solve(level: &Level, budget: &Budget) -> Verdict {
+    let mut frontier: Vec<Vec<Placement>> = vec![Vec::new()];
+    let mut seen: HashSet<Vec<Placement>> = HashSet::new();
+    let mut attempts = 0;
+
+    for size in 0..=budget.placements {
+        let mut next: Vec<Vec<Placement>> = Vec::new();
+        for placements in frontier {
+            attempts += 1;
+            if attempts > budget.attempts {
+                return Verdict::GaveUp;
+            }
+
+            let trial = attempt(level, &placements, budget.ticks);
+            if let Some(ticks) = trial.won {
+                let mut map = level.map.clone();
+                map.decrab();
+                for (position, scenery) in placements.iter() {
+                    map.update(position, *scenery).ok();
+                }
+                map.instantaneous(&level.entities);
+                return Verdict::Solved(Box::new(Solution {
+                    placements,
+                    ticks,
+                    map,
+                }));
+            }
+
+            // Larger combinations include one more tile somewhere the crabs went (there's no
+            // need to keep more of them than there are attempts left to try them with)
+            if size < budget.placements && next.len() <= budget.attempts {
+                for &cell in trial.trail.iter() {
+                    for &scenery in trial.stock.iter() {
+                        let mut larger = placements.clone();
+                        larger.push((cell, scenery));
+                        larger.sort_by_key(|&(position, scenery)| (position, scenery.to_char()));
+                        if seen.insert(larger.clone()) {
+                            next.push(larger);
+                        }
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    if level.skills.is_empty() {
+        Verdict::Unsolvable
+    } else {
+        Verdict::SkillsUntried
+    }
+}
+
+fn attempt(level: &Level, placements: &[Placement], ticks: usize) -> Trial {
+    let mut level = level.clone();
+    level.map.decrab();
+    level.map.track();
+    let mut simulation = Simulation::new(level);
+
+    for &(position, scenery) in placements.iter() {
+        if let Event::Rejected { .. } = simulation.apply_edit(Edit::Place { position, scenery }) {
+            // Combination can't be placed, so it can't lead anywhere either
+            return Trial {
+                won: None,
+                trail: Vec::new(),
+                stock: Vec::new(),
+            };
+        }
+    }
+
+    // Stop early if the crabs get into a loop, since nothing new can happen after that
+    let mut states = HashSet::new();
+    while simulation.outcome() == Outcome::Playing
+        && simulation.tick() < ticks
+        && states.insert(simulation.entities().clone())
+    {
+        simulation.step();
+    }
+
+    let map = simulation.map();
+    let mut trail: Vec<[usize; 2]> = map
+        .trail()
+        .map(|trail| trail.iter().copied().collect())
+        .unwrap_or_default();
+    trail.sort_unstable();
+    Trial {
+        won: if simulation.outcome() == Outcome::Won {
+            Some(simulation.tick())
+        } else {
+            None
+        },
+        trail,
+        stock: Scenery::PLACEABLE
+            .iter()
+            .copied()
+            .filter(|&scenery| map.inventory.remaining(scenery) != Some(0))
+            .collect(),
+    }
+}
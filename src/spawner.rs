@@ -0,0 +1,106 @@
+use crate::{Crab, Entities, Kind, Map, Team};
+
+/// Hatch that lets crabs out into the cell beneath it, one every `interval` ticks
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Spawner {
+    /// Position [y, x] of the hatch tile
+    pub position: [usize; 2],
+    /// Number of crabs to release
+    pub count: usize,
+    /// Ticks between releases
+    pub interval: usize,
+    /// Velocity [vy, vx] crabs set off with
+    pub velocity: [isize; 2],
+    pub kind: Kind,
+    pub team: Option<Team>,
+    released: usize,
+    // Ticks until the next crab is due
+    wait: usize,
+}
+
+impl Spawner {
+    pub fn new(
+        position: [usize; 2],
+        count: usize,
+        interval: usize,
+        velocity: [isize; 2],
+        kind: Kind,
+    ) -> Self {
+        Spawner {
+            position,
+            count,
+            interval,
+            velocity,
+            kind,
+            team: None,
+            released: 0,
+            wait: 0,
+        }
+    }
+
+    /// Crabs still to come out
+    pub fn pending(&self) -> usize {
+        self.count - self.released
+    }
+}
+
+impl Entities {
+    /// Add hatches that release more crabs as the level goes on
+    pub fn with_spawners(mut self, spawners: Vec<Spawner>) -> Self {
+        self.spawners = spawners;
+        self
+    }
+
+    pub fn spawners(&self) -> &[Spawner] {
+        &self.spawners
+    }
+
+    /// Crabs that haven't yet come out of a hatch
+    pub fn pending(&self) -> usize {
+        self.spawners.iter().map(Spawner::pending).sum()
+    }
+
+    /// Crabs that have been in play so far, including any that have since left
+    pub fn released(&self) -> usize {
+        self.created
+    }
+
+    /// Let out any crabs that are due, returning their IDs. A hatch with something in the way
+    /// holds on to its crab until the cell beneath it is clear.
+    pub(crate) fn release(&mut self, map: &Map) -> Vec<usize> {
+        let mut released = Vec::new();
+        for index in 0..self.spawners.len() {
+            let spawner = &self.spawners[index];
+            if spawner.pending() == 0 {
+                continue;
+            }
+            if spawner.wait > 0 {
+                self.spawners[index].wait -= 1;
+                continue;
+            }
+
+            let cell = match map.neighbour(spawner.position, [1, 0]) {
+                Ok(cell) => cell,
+                Err(_) => continue,
+            };
+            let clear = map.look(cell).is_passable()
+                && self.collection.iter().all(|crab| crab.position != cell);
+            if clear {
+                let id = self.created;
+                let mut crab = Crab::new(id, cell, spawner.velocity, spawner.kind);
+                crab.team = spawner.team;
+                self.collection.push(crab);
+                self.created += 1;
+                if spawner.kind == Kind::King {
+                    self.kings.push(id);
+                }
+                released.push(id);
+
+                let spawner = &mut self.spawners[index];
+                spawner.released += 1;
+                spawner.wait = spawner.interval.saturating_sub(1);
+            }
+        }
+        released
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{Crab, Map, Scenery};
+
+/// Ticks after teleporting before a crab can teleport again
+pub const COOLDOWN: usize = 3;
+
+/// Pair of teleporter pads, each sending crabs that move onto it to the other
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Teleporter {
+    pub id: usize,
+    /// Positions [y, x] of the two pads
+    pub pads: [[usize; 2]; 2],
+}
+
+impl Map {
+    /// Pad paired with the teleporter at this cell, if it has one
+    pub fn partner(&self, cell: [usize; 2]) -> Option<[usize; 2]> {
+        self.teleporters
+            .iter()
+            .find_map(|teleporter| match teleporter.pads {
+                [first, second] if first == cell => Some(second),
+                [first, second] if second == cell => Some(first),
+                _ => None,
+            })
+    }
+
+    // Pads placed by the player are paired up in the order they are placed
+    pub(crate) fn pair(&mut self, cell: [usize; 2]) {
+        match self.unpaired.take() {
+            Some(first) => {
+                let id = self
+                    .teleporters
+                    .iter()
+                    .map(|teleporter| teleporter.id + 1)
+                    .max()
+                    .unwrap_or(0);
+                self.teleporters.push(Teleporter {
+                    id,
+                    pads: [first, cell],
+                });
+            }
+            None => self.unpaired = Some(cell),
+        }
+    }
+
+    // Forget a pad that has been taken away, leaving its partner waiting for another
+    pub(crate) fn unpair(&mut self, cell: [usize; 2]) {
+        if self.unpaired == Some(cell) {
+            self.unpaired = None;
+        }
+        if let Some(partner) = self.partner(cell) {
+            self.teleporters
+                .retain(|teleporter| !teleporter.pads.contains(&cell));
+            self.pair(partner);
+        }
+    }
+}
+
+impl Crab {
+    /// Send the crab to the partner of the pad it has just moved onto, keeping its velocity,
+    /// unless it teleported too recently or there's a crab on the other pad
+    pub(crate) fn teleport(&mut self, map: &mut Map) {
+        if self.cooldown > 0 {
+            return;
+        }
+        if let Some(partner) = map.partner(self.position) {
+            if map.look(partner) == Scenery::Teleporter {
+                self.position = partner;
+                self.path.push(partner);
+                map.visit(partner);
+                self.cooldown = COOLDOWN;
+            }
+        }
+    }
+}
@@ -0,0 +1,795 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::format::{LevelError, LevelFile, VERSION};
+use crate::{Scenery, VMAX};
+
+/// A problem found in a level file, with the position in the file it refers to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Line in the file (starting from 1)
+    pub line: usize,
+    /// Column in the line (starting from 1)
+    pub column: usize,
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The file isn't valid RON, or is missing a field
+    Syntax(String),
+    /// The file claims a version of the format this build doesn't understand
+    Version(u32),
+    /// The file has no version, and isn't a legacy level
+    MissingVersion,
+    /// Legacy file has a crab ID missing from one of x, y, vx or vy
+    MissingCrab(u16),
+    EmptyLayout,
+    /// A row of the layout isn't as wide as the first row
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownGlyph {
+        glyph: char,
+    },
+    CrabOutOfBounds {
+        crab: usize,
+        position: [usize; 2],
+    },
+    CrabInScenery {
+        crab: usize,
+        scenery: Scenery,
+    },
+    /// Crab starts faster than terminal velocity
+    CrabTooFast {
+        crab: usize,
+        velocity: [isize; 2],
+    },
+    /// Two crabs start in the same cell
+    CrabsOverlap {
+        crab: usize,
+        other: usize,
+    },
+    DuplicateCrab {
+        id: u16,
+    },
+    /// There is nowhere for the crabs to go
+    NoSafety,
+    /// Spawner isn't on a hatch tile
+    SpawnerOffHatch {
+        spawner: usize,
+        position: [usize; 2],
+    },
+    /// Spawner would release crabs faster than terminal velocity
+    SpawnerTooFast {
+        spawner: usize,
+        velocity: [isize; 2],
+    },
+    /// Spawner would release crabs infinitely fast
+    ZeroInterval {
+        spawner: usize,
+    },
+    /// More crabs must be rescued than the level has, counting those still in hatches
+    QuotaTooHigh {
+        rescue: usize,
+        crabs: usize,
+    },
+    /// Exit isn't on a safety tile
+    ExitOffSafety {
+        name: String,
+        position: [usize; 2],
+    },
+    /// Two exits share a name, or a safety tile
+    DuplicateExit {
+        name: String,
+    },
+    /// Link isn't from a plate, or to a door
+    LinkOffTile {
+        link: usize,
+        position: [usize; 2],
+        expected: char,
+    },
+    /// Teleporter pad isn't on a teleporter tile
+    PadOffTeleporter {
+        id: usize,
+        position: [usize; 2],
+    },
+    /// Two teleporters share an ID
+    DuplicateTeleporter {
+        id: usize,
+    },
+    /// Teleporter tile that isn't one of exactly one pair
+    UnpairedPad {
+        position: [usize; 2],
+    },
+    /// Inventory entry for a glyph the player can't place
+    Unplaceable {
+        glyph: char,
+    },
+    /// A continuous physics setting is out of range
+    Physics {
+        setting: &'static str,
+        value: f64,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Syntax(message) => write!(f, "{}", message),
+            Problem::Version(version) => write!(
+                f,
+                "version {} is not supported (expected {} or earlier)",
+                version, VERSION
+            ),
+            Problem::MissingVersion => write!(f, "missing field `version` (currently {})", VERSION),
+            Problem::MissingCrab(id) => {
+                write!(f, "crab {} needs an entry in each of x, y, vx and vy", id)
+            }
+            Problem::EmptyLayout => write!(f, "layout is empty"),
+            Problem::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} is {} cells wide but the first row is {}",
+                row, found, expected
+            ),
+            Problem::UnknownGlyph { glyph } => {
+                if *glyph == Scenery::StationaryCrab.to_char() {
+                    write!(f, "crabs belong in the crab list, not the layout")
+                } else {
+                    write!(f, "unknown scenery {:?}", glyph)
+                }
+            }
+            Problem::CrabOutOfBounds { crab, position } => write!(
+                f,
+                "crab {} at [{}, {}] is outside the layout",
+                crab, position[0], position[1]
+            ),
+            Problem::CrabInScenery { crab, scenery } => {
+                write!(f, "crab {} starts inside {:?} scenery", crab, scenery)
+            }
+            Problem::CrabTooFast { crab, velocity } => write!(
+                f,
+                "crab {} has velocity {:?} but neither part may be more than {}",
+                crab, velocity, VMAX
+            ),
+            Problem::CrabsOverlap { crab, other } => {
+                write!(f, "crab {} starts in the same cell as crab {}", crab, other)
+            }
+            Problem::DuplicateCrab { id } => write!(f, "crab ID {} is used more than once", id),
+            Problem::NoSafety => {
+                write!(f, "layout has no {} safety tile", Scenery::Safety.to_char())
+            }
+            Problem::SpawnerOffHatch { spawner, position } => write!(
+                f,
+                "spawner {} at [{}, {}] is not on a {} hatch tile",
+                spawner,
+                position[0],
+                position[1],
+                Scenery::Spawner.to_char()
+            ),
+            Problem::SpawnerTooFast { spawner, velocity } => write!(
+                f,
+                "spawner {} has velocity {:?} but neither part may be more than {}",
+                spawner, velocity, VMAX
+            ),
+            Problem::ZeroInterval { spawner } => {
+                write!(f, "spawner {} needs an interval of at least 1", spawner)
+            }
+            Problem::QuotaTooHigh { rescue, crabs } => write!(
+                f,
+                "{} crabs must be rescued but the level only has {}",
+                rescue, crabs
+            ),
+            Problem::ExitOffSafety { name, position } => write!(
+                f,
+                "exit {:?} at [{}, {}] is not on a {} safety tile",
+                name,
+                position[0],
+                position[1],
+                Scenery::Safety.to_char()
+            ),
+            Problem::DuplicateExit { name } => {
+                write!(
+                    f,
+                    "exit {:?} shares its name or tile with another exit",
+                    name
+                )
+            }
+            Problem::LinkOffTile {
+                link,
+                position,
+                expected,
+            } => write!(
+                f,
+                "link {} expects a {} tile at [{}, {}]",
+                link, expected, position[0], position[1]
+            ),
+            Problem::PadOffTeleporter { id, position } => write!(
+                f,
+                "teleporter {} has a pad at [{}, {}] that is not on a {} tile",
+                id,
+                position[0],
+                position[1],
+                Scenery::Teleporter.to_char()
+            ),
+            Problem::DuplicateTeleporter { id } => {
+                write!(f, "teleporter ID {} is used more than once", id)
+            }
+            Problem::UnpairedPad { position } => write!(
+                f,
+                "{} tile at [{}, {}] needs to be in exactly one teleporter pair",
+                Scenery::Teleporter.to_char(),
+                position[0],
+                position[1]
+            ),
+            Problem::Unplaceable { glyph } => {
+                if Scenery::from_char(*glyph).is_some() {
+                    write!(f, "{:?} scenery can't be placed by the player", glyph)
+                } else {
+                    write!(f, "unknown scenery {:?}", glyph)
+                }
+            }
+            Problem::Physics { setting, value } => {
+                let range = if *setting == "gravity" {
+                    format!("above 0 and at most {}", VMAX)
+                } else {
+                    String::from("between 0 and 1")
+                };
+                write!(f, "{} is {} but must be {}", setting, value, range)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.problem)
+    }
+}
+
+/// Check a level file for mistakes, returning every problem found (an empty list means
+/// the level can be played).
+pub fn validate_level(source: &str) -> Vec<Diagnostic> {
+    let locator = Locator::new(source);
+
+    // The file has to decode before anything else can be checked
+    let file = match LevelFile::parse(source) {
+        Ok(file) => file,
+        Err(err) => {
+            let (line, column, problem) = match err {
+                LevelError::Parse(ron::de::Error::Parser(_, position)) => {
+                    // Drop the position from the message as it is reported separately
+                    let message = err.to_string();
+                    let prefix = format!("{}: ", position);
+                    let message = match message.find(&prefix) {
+                        Some(start) => message[start + prefix.len()..].to_string(),
+                        None => message,
+                    };
+                    (position.line, position.col, Problem::Syntax(message))
+                }
+                LevelError::Parse(err) => (1, 1, Problem::Syntax(err.to_string())),
+                LevelError::Io(err) => (1, 1, Problem::Syntax(err.to_string())),
+                LevelError::Version(version) => {
+                    let (line, column) = locator.key("version");
+                    (line, column, Problem::Version(version))
+                }
+                LevelError::MissingVersion => (1, 1, Problem::MissingVersion),
+                LevelError::MissingCrab(id) => {
+                    let (line, column) = locator.legacy_crab(id);
+                    (line, column, Problem::MissingCrab(id))
+                }
+                LevelError::Invalid(diagnostics) => return diagnostics,
+            };
+            return vec![Diagnostic {
+                line,
+                column,
+                problem,
+            }];
+        }
+    };
+
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut report = |(line, column): (usize, usize), problem: Problem| {
+        diagnostics.push(Diagnostic {
+            line,
+            column,
+            problem,
+        })
+    };
+
+    // Layout must be a non-empty rectangle of known glyphs
+    let glyphs = locator.layout();
+    let rows: Vec<&str> = file.layout.lines().collect();
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    if width == 0 {
+        report(locator.key("layout"), Problem::EmptyLayout);
+        return diagnostics;
+    }
+    let mut safety = false;
+    let mut offset = 0;
+    for (y, row) in rows.iter().enumerate() {
+        let found = row.chars().count();
+        if found != width {
+            report(
+                glyphs.at(offset),
+                Problem::RaggedRow {
+                    row: y,
+                    expected: width,
+                    found,
+                },
+            );
+        }
+        for (x, glyph) in row.chars().enumerate() {
+            match Scenery::from_char(glyph) {
+                Some(Scenery::Safety) => safety = true,
+                Some(_) => (),
+                None => report(glyphs.at(offset + x), Problem::UnknownGlyph { glyph }),
+            }
+        }
+        offset += found + 1;
+    }
+    if !safety {
+        report(locator.key("layout"), Problem::NoSafety);
+    }
+
+    // Crabs must start in empty cells within the layout, one per cell
+    let mut ids: HashSet<u16> = HashSet::new();
+    let mut occupied: Vec<([usize; 2], usize)> = Vec::new();
+    for (crab, spec) in file.crabs.iter().enumerate() {
+        let here = locator.crab(crab);
+        if let Some(id) = spec.id {
+            if !ids.insert(id) {
+                report(here, Problem::DuplicateCrab { id });
+            }
+        }
+
+        let position = [spec.pos.0, spec.pos.1];
+        let cell = rows
+            .get(position[0])
+            .and_then(|row| row.chars().nth(position[1]));
+        match cell.map(Scenery::new) {
+            None => report(here, Problem::CrabOutOfBounds { crab, position }),
+            Some(scenery) if scenery.is_passable() => (),
+            Some(scenery) => report(here, Problem::CrabInScenery { crab, scenery }),
+        }
+        let velocity = [spec.vel.0, spec.vel.1];
+        if velocity.iter().any(|v| v.abs() > VMAX) {
+            report(here, Problem::CrabTooFast { crab, velocity });
+        }
+        if let Some(&(_, other)) = occupied.iter().find(|(cell, _)| *cell == position) {
+            report(here, Problem::CrabsOverlap { crab, other });
+        }
+        occupied.push((position, crab));
+    }
+
+    // Spawners must sit on hatches
+    for (spawner, spec) in file.spawners.iter().enumerate() {
+        let here = locator.entry("spawners", spawner);
+        let position = [spec.pos.0, spec.pos.1];
+        let cell = rows
+            .get(position[0])
+            .and_then(|row| row.chars().nth(position[1]));
+        if cell.map(Scenery::new) != Some(Scenery::Spawner) {
+            report(here, Problem::SpawnerOffHatch { spawner, position });
+        }
+        let velocity = [spec.vel.0, spec.vel.1];
+        if velocity.iter().any(|v| v.abs() > VMAX) {
+            report(here, Problem::SpawnerTooFast { spawner, velocity });
+        }
+        if spec.interval == 0 {
+            report(here, Problem::ZeroInterval { spawner });
+        }
+    }
+
+    // The quota has to be reachable with every crab the level starts with or releases
+    if let Some(rescue) = file.rescue {
+        let crabs = file.crabs.len() + file.spawners.iter().map(|spec| spec.count).sum::<usize>();
+        if rescue > crabs {
+            report(
+                locator.key("rescue"),
+                Problem::QuotaTooHigh { rescue, crabs },
+            );
+        }
+    }
+
+    // Exits must sit on safety tiles, and be told apart
+    let mut exits: Vec<(&str, [usize; 2])> = Vec::new();
+    for (exit, spec) in file.exits.iter().enumerate() {
+        let here = locator.entry("exits", exit);
+        let name = spec.name.clone();
+        let position = [spec.pos.0, spec.pos.1];
+        let cell = rows
+            .get(position[0])
+            .and_then(|row| row.chars().nth(position[1]));
+        if cell.map(Scenery::new) != Some(Scenery::Safety) {
+            report(here, Problem::ExitOffSafety { name, position });
+        } else if exits
+            .iter()
+            .any(|&(other, cell)| other == spec.name || cell == position)
+        {
+            report(here, Problem::DuplicateExit { name });
+        }
+        exits.push((&spec.name, position));
+    }
+
+    // Links must join a plate to doors
+    for (link, spec) in file.links.iter().enumerate() {
+        let here = locator.entry("links", link);
+        let ends = std::iter::once((spec.plate, Scenery::Plate.to_char()))
+            .chain(spec.doors.iter().map(|&door| (door, 'D')));
+        for ((y, x), expected) in ends {
+            let cell = rows.get(y).and_then(|row| row.chars().nth(x));
+            let found = match cell.and_then(Scenery::from_char) {
+                Some(Scenery::Plate) => Some('_'),
+                Some(Scenery::Door { .. }) => Some('D'),
+                _ => None,
+            };
+            if found != Some(expected) {
+                report(
+                    here,
+                    Problem::LinkOffTile {
+                        link,
+                        position: [y, x],
+                        expected,
+                    },
+                );
+            }
+        }
+    }
+
+    // Teleporter pads must be on teleporter tiles, and each tile in one pair
+    let mut ids: HashSet<usize> = HashSet::new();
+    let mut pads: Vec<[usize; 2]> = Vec::new();
+    for (teleporter, spec) in file.teleporters.iter().enumerate() {
+        let here = locator.entry("teleporters", teleporter);
+        let id = spec.id;
+        if !ids.insert(id) {
+            report(here, Problem::DuplicateTeleporter { id });
+        }
+        for &(y, x) in [spec.pads.0, spec.pads.1].iter() {
+            let cell = rows.get(y).and_then(|row| row.chars().nth(x));
+            if cell.map(Scenery::new) != Some(Scenery::Teleporter) {
+                report(
+                    here,
+                    Problem::PadOffTeleporter {
+                        id,
+                        position: [y, x],
+                    },
+                );
+            }
+            pads.push([y, x]);
+        }
+    }
+    let mut offset = 0;
+    for (y, row) in rows.iter().enumerate() {
+        for (x, glyph) in row.chars().enumerate() {
+            let uses = pads.iter().filter(|&&pad| pad == [y, x]).count();
+            if Scenery::from_char(glyph) == Some(Scenery::Teleporter) && uses != 1 {
+                report(
+                    glyphs.at(offset + x),
+                    Problem::UnpairedPad { position: [y, x] },
+                );
+            }
+        }
+        offset += row.chars().count() + 1;
+    }
+
+    // Only scenery the player can place belongs in the inventory
+    for &glyph in file.inventory.iter().flat_map(|budget| budget.keys()) {
+        let placeable =
+            Scenery::from_char(glyph).is_some_and(|scenery| Scenery::PLACEABLE.contains(&scenery));
+        if !placeable {
+            report(locator.inventory(glyph), Problem::Unplaceable { glyph });
+        }
+    }
+
+    // Continuous physics settings must keep speeds sensible
+    if let Some(physics) = file.physics.as_ref() {
+        let settings = [
+            (
+                "gravity",
+                physics.gravity,
+                physics.gravity > 0.0 && physics.gravity <= VMAX as f64,
+            ),
+            (
+                "friction",
+                physics.friction,
+                (0.0..=1.0).contains(&physics.friction),
+            ),
+            (
+                "restitution",
+                physics.restitution,
+                (0.0..=1.0).contains(&physics.restitution),
+            ),
+        ];
+        for &(setting, value, valid) in settings.iter() {
+            if !valid {
+                report(locator.key("physics"), Problem::Physics { setting, value });
+            }
+        }
+    }
+
+    // Legacy files key crabs by ID, so repeats would silently replace earlier crabs
+    for (id, position) in locator.legacy_duplicates() {
+        report(position, Problem::DuplicateCrab { id });
+    }
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    diagnostics
+}
+
+// Finds where things are in the source of a level file
+struct Locator<'a> {
+    source: &'a str,
+}
+
+impl<'a> Locator<'a> {
+    fn new(source: &'a str) -> Self {
+        Locator { source }
+    }
+
+    // Line and column (from 1) of a byte offset
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.source[..offset.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before, |start| &before[start + 1..])
+            .chars()
+            .count()
+            + 1;
+        (line, column)
+    }
+
+    // Byte offset of the value following `key:` at the top level of the file
+    fn value_offset(&self, key: &str) -> Option<usize> {
+        let tokens = self.tokens();
+        tokens.iter().enumerate().find_map(|(i, token)| {
+            if token.depth == 1 && &self.source[token.start..token.end] == key {
+                tokens
+                    .get(i + 2)
+                    .filter(|_| self.source[tokens[i + 1].start..].starts_with(':'))
+                    .map(|value| value.start)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn key(&self, key: &str) -> (usize, usize) {
+        self.tokens()
+            .iter()
+            .find(|token| token.depth == 1 && &self.source[token.start..token.end] == key)
+            .map_or((1, 1), |token| self.position(token.start))
+    }
+
+    // Positions of each character of the decoded layout string
+    fn layout(&self) -> Glyphs {
+        let mut offsets: Vec<usize> = Vec::new();
+        if let Some(start) = self.value_offset("layout") {
+            let mut chars = self.source[start..].char_indices().skip(1);
+            while let Some((i, ch)) = chars.next() {
+                match ch {
+                    '"' => break,
+                    '\\' => {
+                        offsets.push(start + i);
+                        // Skip the rest of the escape sequence
+                        if let Some((_, 'u')) = chars.next() {
+                            for (_, ch) in chars.by_ref() {
+                                if ch == '}' {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    _ => offsets.push(start + i),
+                }
+            }
+        }
+        Glyphs {
+            positions: offsets
+                .into_iter()
+                .map(|offset| self.position(offset))
+                .collect(),
+            fallback: self.key("layout"),
+        }
+    }
+
+    // Position of the nth entry in the crab list (or legacy maps)
+    fn crab(&self, n: usize) -> (usize, usize) {
+        let entries = match self.value_offset("crabs") {
+            Some(start) => self.entries(start),
+            None => {
+                // Legacy files list crabs in ID order once loaded
+                let mut keys = self.legacy_keys();
+                keys.sort_by_key(|&(id, _)| id);
+                keys.dedup_by_key(|&mut (id, _)| id);
+                keys.into_iter().map(|(_, offset)| offset).collect()
+            }
+        };
+        entries
+            .get(n)
+            .map_or_else(|| self.key("crabs"), |&offset| self.position(offset))
+    }
+
+    // Position of the nth entry in a list at the top level of the file
+    fn entry(&self, key: &str, n: usize) -> (usize, usize) {
+        self.value_offset(key)
+            .and_then(|start| self.entries(start).get(n).copied())
+            .map_or_else(|| self.key(key), |offset| self.position(offset))
+    }
+
+    // Position of the inventory entry for a glyph
+    fn inventory(&self, glyph: char) -> (usize, usize) {
+        self.value_offset("inventory")
+            .and_then(|start| {
+                // Skip past Some( to the map itself
+                let start = start + self.source[start..].find('{')?;
+                self.entries(start).into_iter().find(|&offset| {
+                    let end = self.source[offset + 1..]
+                        .find('\'')
+                        .map_or(offset, |i| offset + i + 2);
+                    ron::de::from_str::<char>(&self.source[offset..end]) == Ok(glyph)
+                })
+            })
+            .map_or_else(|| self.key("inventory"), |offset| self.position(offset))
+    }
+
+    fn legacy_crab(&self, id: u16) -> (usize, usize) {
+        self.legacy_keys()
+            .into_iter()
+            .find(|&(key, _)| key == id)
+            .map_or_else(|| self.key("x"), |(_, offset)| self.position(offset))
+    }
+
+    fn legacy_duplicates(&self) -> Vec<(u16, (usize, usize))> {
+        let mut duplicates = Vec::new();
+        for key in ["x", "y", "vx", "vy"].iter() {
+            let mut seen: HashSet<i64> = HashSet::new();
+            if let Some(start) = self.value_offset(key) {
+                for offset in self.entries(start) {
+                    if let Some(id) = self.number(offset) {
+                        if !seen.insert(id) {
+                            duplicates.push((id as u16, self.position(offset)));
+                        }
+                    }
+                }
+            }
+        }
+        duplicates
+    }
+
+    // IDs and offsets of crabs in the legacy x map
+    fn legacy_keys(&self) -> Vec<(u16, usize)> {
+        self.value_offset("x")
+            .map(|start| {
+                self.entries(start)
+                    .into_iter()
+                    .filter_map(|offset| self.number(offset).map(|id| (id as u16, offset)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn number(&self, offset: usize) -> Option<i64> {
+        let text: String = self.source[offset..]
+            .chars()
+            .take_while(|ch| ch.is_ascii_digit() || *ch == '-')
+            .collect();
+        text.parse().ok()
+    }
+
+    // Offsets of each element of the list or map opening at `start`
+    fn entries(&self, start: usize) -> Vec<usize> {
+        let tokens = self.tokens();
+        let mut entries = Vec::new();
+        let mut expecting = false;
+        let mut depth = None;
+        for token in tokens.iter().filter(|token| token.start >= start) {
+            let text = &self.source[token.start..token.end];
+            match depth {
+                None => {
+                    depth = Some(token.depth + 1);
+                    expecting = true;
+                }
+                Some(inner) if token.depth < inner => break,
+                Some(inner) if token.depth == inner => {
+                    if text == "," {
+                        expecting = true;
+                    } else if expecting && !matches!(text, "]" | "}" | ")") {
+                        entries.push(token.start);
+                        expecting = false;
+                    }
+                }
+                _ => (),
+            }
+        }
+        entries
+    }
+
+    // Split the source into tokens, skipping whitespace, comments and string contents
+    fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        let mut chars = self.source.char_indices().peekable();
+        while let Some((start, ch)) = chars.next() {
+            match ch {
+                '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                    for (_, ch) in chars.by_ref() {
+                        if ch == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '(' | '[' | '{' => {
+                    tokens.push(Token::new(start, start + 1, depth));
+                    depth += 1;
+                }
+                ')' | ']' | '}' => {
+                    depth = usize::saturating_sub(depth, 1);
+                    tokens.push(Token::new(start, start + 1, depth));
+                }
+                '"' | '\'' => {
+                    let mut end = self.source.len();
+                    let mut escaped = false;
+                    for (i, next) in chars.by_ref() {
+                        if escaped {
+                            escaped = false;
+                        } else if next == '\\' {
+                            escaped = true;
+                        } else if next == ch {
+                            end = i + 1;
+                            break;
+                        }
+                    }
+                    tokens.push(Token::new(start, end, depth));
+                }
+                ',' | ':' => tokens.push(Token::new(start, start + 1, depth)),
+                ch if ch.is_whitespace() => (),
+                _ => {
+                    let mut end = start + ch.len_utf8();
+                    while let Some(&(i, next)) = chars.peek() {
+                        if next.is_alphanumeric() || next == '_' || next == '-' {
+                            end = i + next.len_utf8();
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    tokens.push(Token::new(start, end, depth));
+                }
+            }
+        }
+        tokens
+    }
+}
+
+struct Token {
+    start: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Token {
+    fn new(start: usize, end: usize, depth: usize) -> Self {
+        Token { start, end, depth }
+    }
+}
+
+// Positions in the file of each character in the layout string
+struct Glyphs {
+    positions: Vec<(usize, usize)>,
+    fallback: (usize, usize),
+}
+
+impl Glyphs {
+    fn at(&self, index: usize) -> (usize, usize) {
+        self.positions.get(index).copied().unwrap_or(self.fallback)
+    }
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::{crab, glyph, simulation};
+use crabs::{Edit, Event, Scenery, VMAX};
+
+#[test]
+fn crossing_a_pad_speeds_a_crab_up() {
+    let mut simulation = simulation("#          #\n############", vec![([0, 1], [0, 1])]);
+    simulation.step();
+    assert_eq!(crab(&simulation), ([0, 2], [0, 1]));
+
+    simulation.apply_edit(Edit::Place {
+        position: [0, 3],
+        scenery: Scenery::ForwardBoost,
+    });
+    simulation.step();
+    assert_eq!(crab(&simulation), ([0, 3], [0, 2]));
+
+    // Pad is still there once the crab has moved on
+    simulation.step();
+    assert_eq!(crab(&simulation), ([0, 5], [0, 2]));
+    assert_eq!(glyph(simulation.map(), [0, 3]), '>');
+}
+
+#[test]
+fn crab_on_a_pad_is_not_embedded() {
+    let mut simulation = simulation("#  >       #\n############", vec![([0, 2], [0, 1])]);
+    let events = simulation.step();
+    assert_eq!(crab(&simulation), ([0, 3], [0, 2]));
+    assert!(events.is_empty());
+
+    let events = simulation.step();
+    assert_eq!(crab(&simulation), ([0, 5], [0, 2]));
+    assert!(events.is_empty());
+}
+
+#[test]
+fn moving_against_a_pad_turns_a_crab_around() {
+    let mut simulation = simulation("#    <     #\n############", vec![([0, 4], [0, 2])]);
+    simulation.step();
+    assert_eq!(crab(&simulation), ([0, 4], [0, -3]));
+
+    simulation.step();
+    assert_eq!(crab(&simulation), ([0, 1], [0, -3]));
+}
+
+#[test]
+fn landing_on_a_pad_kicks_a_crab_along() {
+    let mut simulation = simulation("#     #\n#     #\n#  <  #\n#######", vec![([0, 3], [0, 0])]);
+    simulation.step();
+    assert_eq!(crab(&simulation), ([1, 3], [1, 0]));
+
+    simulation.step();
+    assert_eq!(crab(&simulation), ([1, 3], [0, -1]));
+}
+
+#[test]
+fn riding_along_pads_accelerates_up_to_terminal_velocity() {
+    let floor = ">".repeat(60);
+    let layout = format!("{}\n{}", " ".repeat(60), floor);
+    let mut simulation = simulation(&layout, vec![([0, 0], [0, 1])]);
+
+    let mut speeds = Vec::new();
+    for _ in 0..12 {
+        simulation.step();
+        speeds.push(crab(&simulation).1[1]);
+    }
+    assert_eq!(speeds[..3], [2, 3, 4]);
+    assert!(speeds.iter().all(|&speed| speed <= VMAX));
+    assert_eq!(speeds.last(), Some(&VMAX));
+}
+
+#[test]
+fn pad_before_a_wedge_is_crossed_then_the_wedge_is_climbed() {
+    let mut simulation = simulation(
+        "#       #\n#       #\n# >/    #\n#########",
+        vec![([2, 1], [0, 1])],
+    );
+    simulation.step();
+    assert_eq!(crab(&simulation), ([2, 2], [0, 2]));
+
+    // Faster crab climbs the wedge and carries on over the top
+    simulation.step();
+    assert_eq!(crab(&simulation), ([2, 4], [1, 2]));
+}
+
+#[test]
+fn pad_above_a_wedge_is_crossed_at_the_top() {
+    let mut simulation = simulation(
+        "#       #\n#  >    #\n#  /    #\n#########",
+        vec![([2, 2], [0, 1])],
+    );
+    simulation.step();
+    assert_eq!(crab(&simulation), ([1, 3], [0, 2]));
+}
+
+#[test]
+fn pad_overhead_stops_a_crab_climbing() {
+    let mut simulation = simulation(
+        "#       #\n# >     #\n#  /    #\n#########",
+        vec![([2, 2], [0, 1])],
+    );
+    simulation.step();
+    assert_eq!(crab(&simulation), ([2, 2], [0, -1]));
+    assert_eq!(glyph(simulation.map(), [1, 2]), '>');
+}
+
+#[test]
+fn pads_do_not_rescue_or_kill() {
+    let mut simulation = simulation("#    #\n# >  #\n######", vec![([1, 1], [0, 1])]);
+    for _ in 0..20 {
+        for event in simulation.step() {
+            assert!(!matches!(event, Event::Rescued { .. } | Event::Died { .. }));
+        }
+    }
+    assert_eq!(simulation.entities().len(), 1);
+}
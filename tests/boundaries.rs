@@ -0,0 +1,133 @@
+use crabs::format::LevelFile;
+mod common;
+
+use crabs::{
+    user_input, Boundaries, Boundary, Cause, Continuous, Entities, Event, Level, Map, Simulation,
+};
+use termion::event::Key;
+
+fn simulation(
+    layout: &str,
+    boundaries: Boundaries,
+    position: [usize; 2],
+    velocity: [isize; 2],
+) -> Simulation {
+    let mut level = common::level(layout, vec![(position, velocity)]);
+    level.map.boundaries = boundaries;
+    Simulation::new(level)
+}
+
+// Unlike `common::crab`, the crab may have left the map
+fn crab(simulation: &Simulation) -> Option<([usize; 2], [isize; 2])> {
+    common::crabs(simulation).first().copied()
+}
+
+const PIT: &str = "#   X\n##   \n     ";
+
+#[test]
+fn crabs_wrap_around_by_default() {
+    // The block on the top row is the floor beneath the bottom row
+    let mut simulation = simulation("#  \n   \n   ", Boundaries::default(), [1, 0], [0, 0]);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crab(&simulation), Some(([2, 0], [0, 0])));
+}
+
+#[test]
+fn crabs_falling_into_a_bottomless_pit_are_lost() {
+    let boundaries = Boundaries {
+        bottom: Boundary::Kill,
+        ..Boundaries::default()
+    };
+    let mut simulation = simulation(PIT, boundaries, [0, 1], [0, 1]);
+    let mut events = Vec::new();
+    for _ in 0..4 {
+        events.extend(simulation.step());
+    }
+    assert_eq!(crab(&simulation), None);
+    assert!(events.contains(&Event::Died {
+        crab: 0,
+        cause: Cause::OutOfBounds,
+    }));
+}
+
+#[test]
+fn walls_turn_crabs_around() {
+    let boundaries = Boundaries {
+        right: Boundary::Wall,
+        ..Boundaries::default()
+    };
+    let mut simulation = simulation("    \n####", boundaries, [0, 2], [0, 1]);
+    simulation.step();
+    assert_eq!(crab(&simulation), Some(([0, 3], [0, 1])));
+    simulation.step();
+    assert_eq!(crab(&simulation), Some(([0, 3], [0, -1])));
+}
+
+#[test]
+fn walls_are_solid_floors() {
+    let boundaries = Boundaries {
+        bottom: Boundary::Wall,
+        ..Boundaries::default()
+    };
+    let mut simulation = simulation("  X\n   ", boundaries, [0, 0], [0, 0]);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crab(&simulation), Some(([1, 0], [0, 0])));
+}
+
+#[test]
+fn continuous_crabs_respect_boundaries() {
+    let mut map = Map::new("#  X\n#   \n#   ");
+    map.physics.continuous = Some(Continuous::default());
+    map.boundaries.bottom = Boundary::Kill;
+    let mut simulation =
+        Simulation::new(Level::new(Entities::new(vec![[0, 1]], vec![[0, 0]]), map));
+    let mut events = Vec::new();
+    for _ in 0..10 {
+        events.extend(simulation.step());
+    }
+    assert!(events.contains(&Event::Died {
+        crab: 0,
+        cause: Cause::OutOfBounds,
+    }));
+}
+
+#[test]
+fn cursor_stops_at_edges_that_do_not_wrap() {
+    let mut map = Map::new("   \n   ");
+    map.boundaries.left = Boundary::Wall;
+    map.boundaries.top = Boundary::Kill;
+    let mut cursor = [0, 0];
+    user_input(Key::Left, &mut cursor, &map);
+    user_input(Key::Up, &mut cursor, &map);
+    assert_eq!(cursor, [0, 0]);
+
+    user_input(Key::Down, &mut cursor, &map);
+    user_input(Key::Down, &mut cursor, &map);
+    user_input(Key::Right, &mut cursor, &map);
+    user_input(Key::Right, &mut cursor, &map);
+    user_input(Key::Right, &mut cursor, &map);
+    assert_eq!(cursor, [0, 0]);
+}
+
+#[test]
+fn boundaries_are_read_from_level_files() {
+    let file = LevelFile::parse(
+        "(version: 2, boundaries: Some((bottom: Kill, left: Wall)), crabs: [(pos: (0, 1), vel: (0, 1))], layout: \" X\\n##\")",
+    )
+    .unwrap();
+    let level = file.clone().into_level();
+    assert_eq!(
+        level.map.boundaries,
+        Boundaries {
+            top: Boundary::Wrap,
+            bottom: Boundary::Kill,
+            left: Boundary::Wall,
+            right: Boundary::Wrap,
+        }
+    );
+    assert_eq!(LevelFile::from_level(&level).boundaries, file.boundaries);
+}
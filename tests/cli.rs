@@ -0,0 +1,105 @@
+use std::process;
+use std::time;
+
+use crabs::cli::{Cli, CliError, Command, EXIT_FAILURE, EXIT_SUCCESS, EXIT_USAGE};
+
+fn parse(args: &[&str]) -> Result<Cli, CliError> {
+    Cli::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+// Exit code of the game run with these arguments
+fn status(args: &[&str]) -> i32 {
+    process::Command::new(env!("CARGO_BIN_EXE_crabs"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+        .unwrap()
+}
+
+#[test]
+fn options_can_come_before_or_after_the_subcommand() {
+    let before = parse(&["--seed", "7", "--no-colour", "solve", "a.ron"]).unwrap();
+    let after = parse(&["solve", "a.ron", "--no-colour", "--seed", "7"]).unwrap();
+    assert_eq!(before, after);
+    assert_eq!(before.command, Command::Solve(vec![String::from("a.ron")]));
+    assert_eq!(before.options.seed, Some(7));
+    assert!(!before.options.colour);
+}
+
+#[test]
+fn values_can_follow_an_equals_sign() {
+    let cli = parse(&["--tick-time=50", "--level=3", "--pack", "Extras"]).unwrap();
+    assert_eq!(cli.options.tick_time, time::Duration::from_millis(50));
+    assert_eq!(cli.options.level, Some(3));
+    assert_eq!(cli.options.pack.as_deref(), Some("Extras"));
+    assert_eq!(cli.command, Command::Play(Vec::new()));
+
+    assert_eq!(
+        parse(&["--no-colour=yes"]),
+        Err(CliError::UnexpectedValue(String::from("--no-colour")))
+    );
+    assert_eq!(
+        parse(&["--seed"]),
+        Err(CliError::MissingValue(String::from("--seed")))
+    );
+}
+
+#[test]
+fn levels_are_counted_from_one() {
+    assert_eq!(
+        parse(&["--level", "0"]),
+        Err(CliError::InvalidValue {
+            option: String::from("--level"),
+            value: String::from("0"),
+        })
+    );
+    assert_eq!(
+        parse(&["--level", "first"]),
+        Err(CliError::InvalidValue {
+            option: String::from("--level"),
+            value: String::from("first"),
+        })
+    );
+}
+
+#[test]
+fn unknown_options_are_refused() {
+    assert_eq!(
+        parse(&["play", "--fast"]),
+        Err(CliError::UnknownOption(String::from("--fast")))
+    );
+    // Unless they come after --
+    assert_eq!(
+        parse(&["--", "--fast"]).unwrap().command,
+        Command::Play(vec![String::from("--fast")])
+    );
+}
+
+#[test]
+fn subcommands_that_need_a_file_say_so() {
+    assert_eq!(parse(&["check"]), Err(CliError::MissingFile("check")));
+    assert_eq!(parse(&["replay"]), Err(CliError::MissingFile("replay")));
+    assert_eq!(
+        parse(&["replay", "a.replay", "b.replay"]),
+        Err(CliError::UnexpectedArgument(String::from("b.replay")))
+    );
+    assert_eq!(parse(&["edit"]).unwrap().command, Command::Edit(None));
+}
+
+#[test]
+fn errors_map_to_exit_codes() {
+    assert_eq!(CliError::Help.exit_code(), EXIT_SUCCESS);
+    assert_eq!(CliError::Version.exit_code(), EXIT_SUCCESS);
+    assert_eq!(CliError::MissingFile("check").exit_code(), EXIT_USAGE);
+
+    assert_eq!(status(&["--version"]), EXIT_SUCCESS);
+    assert_eq!(status(&["--fast"]), EXIT_USAGE);
+    assert_eq!(status(&["check"]), EXIT_USAGE);
+    assert_eq!(
+        status(&["check", "examples/custom_level.ron"]),
+        EXIT_SUCCESS
+    );
+    assert_eq!(status(&["check", "examples/pack.ron"]), EXIT_FAILURE);
+}
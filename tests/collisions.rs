@@ -0,0 +1,104 @@
+mod common;
+
+use common::{crabs, simulation};
+use crabs::Simulation;
+
+// Same as `crabs`, but without caring which crab is which
+fn sorted(simulation: &Simulation) -> Vec<([usize; 2], [isize; 2])> {
+    let mut crabs = crabs(simulation);
+    crabs.sort();
+    crabs
+}
+
+const CORRIDOR: &str = "#         #\n###########";
+
+#[test]
+fn crabs_meeting_head_on_swap_speeds() {
+    let mut simulation = simulation(CORRIDOR, vec![([0, 2], [0, 1]), ([0, 6], [0, -1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), [([0, 3], [0, 1]), ([0, 5], [0, -1])]);
+
+    // Both want the same cell, so both stay put and bounce apart
+    simulation.step();
+    assert_eq!(crabs(&simulation), [([0, 3], [0, -1]), ([0, 5], [0, 1])]);
+
+    simulation.step();
+    assert_eq!(crabs(&simulation), [([0, 2], [0, -1]), ([0, 6], [0, 1])]);
+}
+
+#[test]
+fn neighbours_meeting_head_on_swap_speeds() {
+    let mut simulation = simulation(CORRIDOR, vec![([0, 3], [0, 1]), ([0, 4], [0, -1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), [([0, 3], [0, -1]), ([0, 4], [0, 1])]);
+}
+
+#[test]
+fn fast_crab_passes_its_speed_on() {
+    let mut simulation = simulation(CORRIDOR, vec![([0, 1], [0, 3]), ([0, 4], [0, 1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), [([0, 3], [0, 1]), ([0, 5], [0, 3])]);
+}
+
+#[test]
+fn crabs_walking_in_a_line_keep_going() {
+    let mut simulation = simulation(CORRIDOR, vec![([0, 2], [0, 1]), ([0, 3], [0, 1])]);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crabs(&simulation), [([0, 4], [0, 1]), ([0, 6], [0, 1])]);
+}
+
+#[test]
+fn crabs_stand_on_each_other() {
+    let mut simulation = simulation(
+        "#    #\n#    #\n#    #\n######",
+        vec![([0, 2], [0, 0]), ([2, 2], [0, 0])],
+    );
+    for _ in 0..4 {
+        simulation.step();
+    }
+    assert_eq!(crabs(&simulation), [([1, 2], [0, 0]), ([2, 2], [0, 0])]);
+    assert_eq!(
+        simulation.map().to_string(),
+        "#    #\n# .  #\n# .  #\n######"
+    );
+}
+
+#[test]
+fn crabs_fall_once_the_crab_below_walks_off() {
+    let mut simulation = simulation(
+        "#    #\n#    #\n######",
+        vec![([0, 2], [0, 0]), ([1, 2], [0, 1])],
+    );
+    simulation.step();
+    assert_eq!(crabs(&simulation), [([0, 2], [0, 0]), ([1, 3], [0, 1])]);
+
+    simulation.step();
+    assert_eq!(crabs(&simulation)[0], ([1, 2], [1, 0]));
+}
+
+#[test]
+fn order_of_the_crab_list_does_not_matter() {
+    let layout =
+        "#                  #\n#   /   @    \\     #\n#     >    <       #\n####################";
+    let crabs = vec![
+        ([0, 1], [0, 2]),
+        ([0, 5], [1, -1]),
+        ([0, 9], [0, 1]),
+        ([1, 2], [0, 1]),
+        ([1, 10], [0, -2]),
+        ([2, 3], [0, 1]),
+        ([2, 14], [0, -1]),
+        ([0, 17], [0, -3]),
+    ];
+
+    let mut forward = simulation(layout, crabs.clone());
+    let mut backward = simulation(layout, crabs.into_iter().rev().collect());
+    for _ in 0..200 {
+        forward.step();
+        backward.step();
+        assert_eq!(sorted(&forward), sorted(&backward));
+        assert_eq!(forward.map().to_string(), backward.map().to_string());
+    }
+}
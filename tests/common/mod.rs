@@ -0,0 +1,45 @@
+// Fixtures shared by the integration tests (each test file uses some of them)
+#![allow(dead_code)]
+
+use crabs::{Entities, Level, Map, Simulation};
+
+/// Level with crabs at the given positions and velocities, and everything else left at its
+/// defaults
+pub fn level(layout: &str, crabs: Vec<([usize; 2], [isize; 2])>) -> Level {
+    Level::new(
+        Entities::new(
+            crabs.iter().map(|crab| crab.0).collect(),
+            crabs.iter().map(|crab| crab.1).collect(),
+        ),
+        Map::new(layout),
+    )
+}
+
+pub fn simulation(layout: &str, crabs: Vec<([usize; 2], [isize; 2])>) -> Simulation {
+    Simulation::new(level(layout, crabs))
+}
+
+/// Positions and velocities of the crabs still in play, in level order
+pub fn crabs(simulation: &Simulation) -> Vec<([usize; 2], [isize; 2])> {
+    simulation
+        .entities()
+        .iter()
+        .map(|crab| (crab.position(), crab.velocity()))
+        .collect()
+}
+
+/// Position and velocity of the first crab still in play
+pub fn crab(simulation: &Simulation) -> ([usize; 2], [isize; 2]) {
+    *crabs(simulation).first().expect("crab is still in play")
+}
+
+/// Glyph shown in a cell of the map
+pub fn glyph(map: &Map, cell: [usize; 2]) -> char {
+    let map = map.to_string();
+    map.lines()
+        .nth(cell[0])
+        .unwrap()
+        .chars()
+        .nth(cell[1])
+        .unwrap()
+}
@@ -0,0 +1,514 @@
+use std::env;
+use std::fs;
+
+use termion::event::Key;
+
+mod common;
+
+use crabs::editor::{Editor, Mode, Request};
+use crabs::format::{self, LevelFile};
+use crabs::{Kind, Outcome};
+
+fn press(editor: &mut Editor, keys: &[Key]) {
+    for &key in keys {
+        assert_eq!(editor.press(key), None);
+    }
+}
+
+fn glyph(editor: &Editor, cell: [usize; 2]) -> char {
+    common::glyph(&editor.level().map, cell)
+}
+
+fn temp_file(name: &str) -> String {
+    let path = env::temp_dir().join(format!("crabs-{}-{}.ron", name, std::process::id()));
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn arrows_move_the_cursor_and_glyphs_paint_under_it() {
+    let mut editor = Editor::blank();
+    let start = editor.cursor();
+    for ch in [
+        '#', '/', '\\', '>', '<', '^', '@', '~', '%', 'X', '_', 'D', 'd', '}', '(', 'H', 'T',
+    ]
+    .iter()
+    {
+        press(&mut editor, &[Key::Char(*ch)]);
+        assert_eq!(glyph(&editor, editor.cursor()), *ch);
+        press(&mut editor, &[Key::Right]);
+    }
+    assert_eq!(editor.cursor(), [start[0], start[1] + 17]);
+
+    // A space clears the cell
+    press(&mut editor, &[Key::Left, Key::Char(' ')]);
+    assert_eq!(glyph(&editor, editor.cursor()), ' ');
+}
+
+#[test]
+fn crabs_are_placed_then_steered() {
+    let mut editor = Editor::blank();
+    press(&mut editor, &[Key::Up, Key::Char('.')]);
+    assert_eq!(editor.mode(), Mode::Steer);
+
+    // Velocities can be negative and more than one digit, up to the speed limit
+    press(&mut editor, &[Key::Up]);
+    press(&mut editor, &[Key::Right; 12]);
+    press(&mut editor, &[Key::Char('\n')]);
+    assert_eq!(editor.mode(), Mode::Paint);
+
+    let crab = editor.level().entities.iter().next().unwrap();
+    assert_eq!(crab.position(), [11, 40]);
+    assert_eq!(crab.velocity(), [-1, 10]);
+    assert_eq!(editor.level().rescue, 1);
+
+    // Typing another crab glyph on the crab changes its kind
+    press(&mut editor, &[Key::Char('K')]);
+    assert_eq!(editor.level().entities.len(), 1);
+    let crab = editor.level().entities.iter().next().unwrap();
+    assert_eq!(crab.kind(), Kind::King);
+    assert_eq!(crab.velocity(), [-1, 10]);
+}
+
+#[test]
+fn scenery_cannot_be_painted_over_a_crab() {
+    let mut editor = Editor::blank();
+    press(
+        &mut editor,
+        &[Key::Char('O'), Key::Char('\n'), Key::Char('#')],
+    );
+    assert_eq!(glyph(&editor, editor.cursor()), ' ');
+    assert!(!editor.message().is_empty());
+
+    // Passable scenery is fine
+    press(&mut editor, &[Key::Char('>')]);
+    assert_eq!(glyph(&editor, editor.cursor()), '>');
+}
+
+#[test]
+fn hatches_can_be_steered_and_counted() {
+    let mut editor = Editor::blank();
+    press(
+        &mut editor,
+        &[Key::Char('H'), Key::Char('+'), Key::Char('+')],
+    );
+    press(
+        &mut editor,
+        &[Key::Char('v'), Key::Left, Key::Left, Key::Char('v')],
+    );
+
+    let spawner = &editor.level().entities.spawners()[0];
+    assert_eq!(spawner.position, editor.cursor());
+    assert_eq!(spawner.count, 3);
+    assert_eq!(spawner.velocity, [0, -1]);
+    assert_eq!(editor.level().rescue, 3);
+
+    // A hatch always lets out at least one crab
+    press(&mut editor, &[Key::Char('-'); 5]);
+    assert_eq!(editor.level().entities.spawners()[0].count, 1);
+
+    // Painting over the hatch removes it
+    press(&mut editor, &[Key::Char('#')]);
+    assert!(editor.level().entities.spawners().is_empty());
+    assert_eq!(editor.level().rescue, 0);
+}
+
+#[test]
+fn teleporters_pair_in_the_order_they_are_painted() {
+    let mut editor = Editor::blank();
+    let first = editor.cursor();
+    press(&mut editor, &[Key::Char('T'), Key::Right, Key::Char('T')]);
+    let second = editor.cursor();
+    assert_eq!(editor.level().map.partner(first), Some(second));
+
+    // A pad that loses its partner pairs with the next one painted
+    press(&mut editor, &[Key::Char(' '), Key::Right, Key::Char('T')]);
+    let third = editor.cursor();
+    assert_eq!(editor.level().map.partner(second), None);
+    assert_eq!(editor.level().map.partner(first), Some(third));
+    assert_eq!(editor.level().map.teleporters.len(), 1);
+}
+
+#[test]
+fn existing_levels_are_opened_for_editing() {
+    let path = temp_file("open");
+    let source = r#"(
+    version: 2,
+    crabs: [(pos: (0, 0), vel: (0, -2), kind: Heavy)],
+    exits: [(name: "north", pos: (0, 4)), (name: "south", pos: (1, 4))],
+    layout: "    X\n    X\n#####",
+)"#;
+    fs::write(&path, source).unwrap();
+    let mut editor = Editor::open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let crab = editor.level().entities.iter().next().unwrap();
+    assert_eq!(crab.kind(), Kind::Heavy);
+    assert_eq!(crab.velocity(), [0, -2]);
+    assert_eq!(editor.level().map.exits.len(), 2);
+
+    // Painting over an exit forgets it
+    let cursor = editor.cursor();
+    press(&mut editor, &[Key::Right; 2]);
+    assert_eq!(editor.cursor(), [cursor[0], 4]);
+    press(&mut editor, &[Key::Char('#')]);
+    let exits = &editor.level().map.exits;
+    assert_eq!(exits.len(), 1);
+    assert_eq!(exits[0].name, "north");
+}
+
+// Open a level file that `check` would reject
+fn open(name: &str, source: &str) -> Editor {
+    let path = temp_file(name);
+    fs::write(&path, source).unwrap();
+    let editor = Editor::open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    editor
+}
+
+#[test]
+fn levels_without_a_layout_open_on_a_blank_map() {
+    let mut editor = open("empty", r#"(version: 2, crabs: [], layout: "")"#);
+    assert!(!editor.inspect().is_empty());
+    press(&mut editor, &[Key::Right, Key::Char('#')]);
+    assert_eq!(glyph(&editor, editor.cursor()), '#');
+}
+
+#[test]
+fn anything_outside_the_layout_is_left_out() {
+    let mut editor = open(
+        "outside",
+        r#"(
+    version: 2,
+    crabs: [(pos: (0, 1), vel: (0, 1))],
+    spawners: [(pos: (5, 0), count: 1, interval: 1, vel: (0, 1))],
+    exits: [(name: "in", pos: (0, 2)), (name: "out", pos: (0, 9))],
+    links: [(plate: (9, 9), doors: []), (plate: (0, 0), doors: [(0, 3), (9, 0)])],
+    teleporters: [(id: 0, pads: ((0, 3), (7, 7)))],
+    layout: "_ XD\n####",
+)"#,
+    );
+    let map = &editor.level().map;
+    assert_eq!(map.exits.len(), 1);
+    assert_eq!(map.links.len(), 1);
+    assert_eq!(map.links[0].doors, [[0, 3]]);
+    assert!(map.teleporters.is_empty());
+    assert_eq!(editor.message(), "Left out 5 things outside the layout");
+
+    // Painting tidies up without tripping over anything
+    press(&mut editor, &[Key::Char('#')]);
+    assert_eq!(editor.level().entities.len(), 1);
+}
+
+#[test]
+fn crabs_outside_the_layout_stay_out_of_play_tests() {
+    let mut editor = open(
+        "stray",
+        r#"(
+    version: 2,
+    crabs: [(pos: (0, 1), vel: (0, 1)), (pos: (4, 7), vel: (0, 1))],
+    layout: "   X\n####",
+)"#,
+    );
+    assert_eq!(editor.level().entities.len(), 1);
+    assert_eq!(editor.level().rescue, 1);
+    press(&mut editor, &[Key::Char('t')]);
+    for _ in 0..5 {
+        editor.tick();
+    }
+    assert_eq!(editor.trial().unwrap().outcome(), Outcome::Won);
+}
+
+#[test]
+fn edited_levels_save_and_load() {
+    let path = temp_file("save");
+    let mut editor = Editor::blank();
+    press(&mut editor, &[Key::Char('*')]);
+    press(
+        &mut editor,
+        &[Key::Down, Key::Down, Key::Left, Key::Char('\n')],
+    );
+    press(
+        &mut editor,
+        &[Key::Down, Key::Char('#'), Key::Right, Key::Char('X')],
+    );
+    format::save_level(&path, editor.level()).unwrap();
+    let saved = fs::read_to_string(&path).unwrap();
+    let reopened = Editor::open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        LevelFile::from_level(reopened.level()).to_ron(),
+        LevelFile::parse(&saved).unwrap().to_ron()
+    );
+    let crab = reopened.level().entities.iter().next().unwrap();
+    assert_eq!(crab.kind(), Kind::Floater);
+    assert_eq!(crab.velocity(), [2, -1]);
+    assert_eq!(
+        reopened.level().map.to_string(),
+        editor.level().map.to_string()
+    );
+    assert!(saved.contains("rescue: None"));
+}
+
+#[test]
+fn save_and_quit_are_left_to_the_caller() {
+    let mut editor = Editor::blank();
+    let blank = editor.level().map.to_string();
+    assert_eq!(editor.press(Key::Char('w')), Some(Request::Save));
+    assert_eq!(editor.press(Key::Char('q')), Some(Request::Quit));
+    assert_eq!(editor.level().map.to_string(), blank);
+}
+
+#[test]
+fn changes_can_be_undone_and_redone() {
+    let mut editor = Editor::blank();
+    let cell = editor.cursor();
+    press(
+        &mut editor,
+        &[Key::Char('#'), Key::Char('X'), Key::Char('u')],
+    );
+    assert_eq!(glyph(&editor, cell), '#');
+    press(&mut editor, &[Key::Char('u')]);
+    assert_eq!(glyph(&editor, cell), ' ');
+    press(&mut editor, &[Key::Char('u')]);
+    assert_eq!(editor.message(), "Nothing to undo");
+
+    press(&mut editor, &[Key::Ctrl('r'), Key::Ctrl('r')]);
+    assert_eq!(glyph(&editor, cell), 'X');
+    press(&mut editor, &[Key::Ctrl('r')]);
+    assert_eq!(editor.message(), "Nothing to redo");
+
+    // Steering a crab is undone a step at a time
+    press(
+        &mut editor,
+        &[
+            Key::Down,
+            Key::Char('Y'),
+            Key::Right,
+            Key::Right,
+            Key::Char('u'),
+        ],
+    );
+    let crab = editor.level().entities.iter().next().unwrap();
+    assert_eq!(crab.velocity(), [0, 1]);
+}
+
+#[test]
+fn delete_removes_the_crab_or_clears_the_cell() {
+    let mut editor = Editor::blank();
+    press(
+        &mut editor,
+        &[Key::Char('>'), Key::Char('K'), Key::Char('\n')],
+    );
+    assert_eq!(editor.level().rescue, 1);
+
+    press(&mut editor, &[Key::Delete]);
+    assert_eq!(editor.level().entities.len(), 0);
+    assert_eq!(editor.level().rescue, 0);
+    assert_eq!(glyph(&editor, editor.cursor()), '>');
+
+    press(&mut editor, &[Key::Backspace]);
+    assert_eq!(glyph(&editor, editor.cursor()), ' ');
+}
+
+fn count(editor: &Editor, ch: char) -> usize {
+    editor
+        .level()
+        .map
+        .to_string()
+        .chars()
+        .filter(|&glyph| glyph == ch)
+        .count()
+}
+
+#[test]
+fn boxes_are_filled_and_outlined() {
+    let mut editor = Editor::blank();
+    let [y, x] = editor.cursor();
+    press(
+        &mut editor,
+        &[Key::Char('s'), Key::Right, Key::Right, Key::Down],
+    );
+    press(&mut editor, &[Key::Right, Key::Down]);
+    assert_eq!(editor.selection(), Some([[y, x], [y + 2, x + 3]]));
+    press(&mut editor, &[Key::Char('b'), Key::Char('#')]);
+    assert_eq!(count(&editor, '#'), 12);
+    assert_eq!(editor.tool(), None);
+
+    // The whole fill is undone at once
+    press(&mut editor, &[Key::Char('u')]);
+    assert_eq!(count(&editor, '#'), 0);
+
+    press(&mut editor, &[Key::Char('o'), Key::Char('~')]);
+    assert_eq!(count(&editor, '~'), 10);
+    assert_eq!(glyph(&editor, [y + 1, x + 1]), ' ');
+    assert_eq!(glyph(&editor, [y + 1, x + 3]), '~');
+}
+
+#[test]
+fn tools_need_a_box_and_can_be_cancelled() {
+    let mut editor = Editor::blank();
+    press(&mut editor, &[Key::Char('b')]);
+    assert_eq!(editor.tool(), None);
+    assert!(!editor.message().is_empty());
+
+    press(&mut editor, &[Key::Char('s'), Key::Char('l'), Key::Esc]);
+    assert_eq!(editor.tool(), None);
+    assert_eq!(editor.selection(), None);
+    press(&mut editor, &[Key::Char('#')]);
+    assert_eq!(count(&editor, '#'), 1);
+}
+
+#[test]
+fn lines_run_from_the_marked_corner_to_the_cursor() {
+    let mut editor = Editor::blank();
+    let [y, x] = editor.cursor();
+    press(&mut editor, &[Key::Char('s'), Key::Down, Key::Down]);
+    press(&mut editor, &[Key::Right; 4]);
+    press(&mut editor, &[Key::Char('l'), Key::Char('/')]);
+    assert_eq!(count(&editor, '/'), 5);
+    for cell in [
+        [y, x],
+        [y + 1, x + 1],
+        [y + 1, x + 2],
+        [y + 2, x + 3],
+        [y + 2, x + 4],
+    ]
+    .iter()
+    {
+        assert_eq!(glyph(&editor, *cell), '/');
+    }
+}
+
+#[test]
+fn flood_fill_stays_inside_walls() {
+    let mut editor = Editor::blank();
+    let [y, x] = editor.cursor();
+    press(&mut editor, &[Key::Char('s')]);
+    press(&mut editor, &[Key::Right; 4]);
+    press(&mut editor, &[Key::Down; 3]);
+    press(&mut editor, &[Key::Char('o'), Key::Char('#'), Key::Esc]);
+    press(
+        &mut editor,
+        &[Key::Left, Key::Up, Key::Char('f'), Key::Char('~')],
+    );
+    assert_eq!(count(&editor, '~'), 6);
+    assert_eq!(glyph(&editor, [y + 1, x + 1]), '~');
+    assert_eq!(glyph(&editor, [y - 1, x]), ' ');
+
+    // Flooding the outside fills the rest of the map
+    press(&mut editor, &[Key::Up; 3]);
+    press(&mut editor, &[Key::Char('f'), Key::Char('^')]);
+    assert_eq!(count(&editor, '^'), 80 * 24 - 20);
+}
+
+#[test]
+fn copies_are_pasted_turned_and_mirrored() {
+    let mut editor = Editor::blank();
+    press(&mut editor, &[Key::Char('/'), Key::Right, Key::Char('}')]);
+    press(
+        &mut editor,
+        &[Key::Char('s'), Key::Left, Key::Char('c'), Key::Esc],
+    );
+
+    press(&mut editor, &[Key::Down, Key::Char('p')]);
+    let [y, x] = editor.cursor();
+    assert_eq!(
+        (glyph(&editor, [y, x]), glyph(&editor, [y, x + 1])),
+        ('/', '}')
+    );
+
+    press(&mut editor, &[Key::Down, Key::Char('m'), Key::Char('p')]);
+    assert_eq!(
+        (glyph(&editor, [y + 1, x]), glyph(&editor, [y + 1, x + 1])),
+        ('{', '\\')
+    );
+
+    press(
+        &mut editor,
+        &[Key::Down, Key::Down, Key::Char('r'), Key::Char('p')],
+    );
+    assert_eq!(
+        (glyph(&editor, [y + 3, x]), glyph(&editor, [y + 4, x])),
+        ('{', '/')
+    );
+    assert_eq!(glyph(&editor, [y + 3, x + 1]), ' ');
+}
+
+#[test]
+fn tools_leave_crabs_alone() {
+    let mut editor = Editor::blank();
+    let [y, x] = editor.cursor();
+    press(
+        &mut editor,
+        &[Key::Char('.'), Key::Char('\n'), Key::Char('s')],
+    );
+    press(
+        &mut editor,
+        &[Key::Right, Key::Down, Key::Char('b'), Key::Char('#')],
+    );
+    assert_eq!(count(&editor, '#'), 3);
+    assert_eq!(glyph(&editor, [y, x]), ' ');
+    assert_eq!(editor.level().entities.len(), 1);
+}
+
+#[test]
+fn play_tests_run_on_a_copy_of_the_level() {
+    let mut editor = Editor::blank();
+    let [y, x] = editor.cursor();
+    press(&mut editor, &[Key::Down, Key::Char('s')]);
+    press(&mut editor, &[Key::Right; 3]);
+    press(&mut editor, &[Key::Char('b'), Key::Char('#'), Key::Esc]);
+    press(
+        &mut editor,
+        &[Key::Up, Key::Char('X'), Key::Left, Key::Left],
+    );
+    press(
+        &mut editor,
+        &[Key::Left, Key::Char('.'), Key::Right, Key::Char('\n')],
+    );
+    let before = editor.level().map.to_string();
+
+    press(&mut editor, &[Key::Char('t')]);
+    assert!(editor.trial().is_some());
+    for _ in 0..3 {
+        editor.tick();
+    }
+    let trial = editor.trial().unwrap();
+    assert_eq!(trial.outcome(), Outcome::Won);
+    assert_eq!(trial.rescued(), 1);
+
+    // Editing is left alone until the play-test is over
+    press(&mut editor, &[Key::Char('#'), Key::Char('t')]);
+    assert!(editor.trial().is_none());
+    assert_eq!(editor.level().map.to_string(), before);
+    let crab = editor.level().entities.iter().next().unwrap();
+    assert_eq!(crab.position(), [y, x]);
+    assert_eq!(crab.velocity(), [0, 1]);
+}
+
+#[test]
+fn play_tests_can_be_paused_and_stepped() {
+    let mut editor = Editor::blank();
+    press(
+        &mut editor,
+        &[Key::Char('.'), Key::Char('\n'), Key::Char('t')],
+    );
+    editor.tick();
+    assert_eq!(editor.trial().unwrap().tick(), 1);
+
+    press(&mut editor, &[Key::Char(' ')]);
+    assert!(editor.is_paused());
+    assert!(editor.tick().is_empty());
+    assert_eq!(editor.trial().unwrap().tick(), 1);
+    press(&mut editor, &[Key::Char('n'), Key::Char('n')]);
+    assert_eq!(editor.trial().unwrap().tick(), 3);
+
+    press(&mut editor, &[Key::Char(' ')]);
+    editor.tick();
+    assert_eq!(editor.trial().unwrap().tick(), 4);
+
+    // Each play-test starts afresh
+    press(&mut editor, &[Key::Esc, Key::Char('t')]);
+    assert_eq!(editor.trial().unwrap().tick(), 0);
+}
@@ -0,0 +1,211 @@
+use crabs::format::LevelFile;
+use crabs::{Cause, Entities, Event, Exit, Kind, Level, Map, Outcome, Simulation, Spawner, Team};
+
+fn level(
+    layout: &str,
+    crabs: Vec<([usize; 2], [isize; 2], Option<Team>)>,
+    exits: Vec<Exit>,
+) -> Level {
+    let mut map = Map::new(layout);
+    map.exits = exits;
+    Level::new(
+        Entities::new(
+            crabs.iter().map(|crab| crab.0).collect(),
+            crabs.iter().map(|crab| crab.1).collect(),
+        )
+        .with_teams(crabs.iter().map(|crab| crab.2).collect()),
+        map,
+    )
+}
+
+// Every crab leaving the level, by crab ID
+fn departures(simulation: &mut Simulation, ticks: usize) -> Vec<Event> {
+    let mut events = Vec::new();
+    for _ in 0..ticks {
+        events.extend(
+            simulation
+                .step()
+                .into_iter()
+                .filter(|event| matches!(event, Event::Rescued { .. } | Event::Died { .. })),
+        );
+    }
+    events.sort_by_key(|event| match event {
+        Event::Rescued { crab, .. } | Event::Died { crab, .. } => *crab,
+        _ => 0,
+    });
+    events
+}
+
+#[test]
+fn unnamed_safety_takes_any_crab() {
+    let mut simulation = Simulation::new(level(
+        "  X\n###",
+        vec![([0, 0], [0, 1], Some(Team::Red))],
+        Vec::new(),
+    ));
+    assert_eq!(
+        departures(&mut simulation, 3),
+        vec![Event::Rescued {
+            crab: 0,
+            exit: None
+        }]
+    );
+    assert_eq!(simulation.outcome(), Outcome::Won);
+}
+
+#[test]
+fn exit_records_the_crabs_that_used_it() {
+    let mut simulation = Simulation::new(level(
+        "  X  \n#####",
+        vec![([0, 1], [0, 1], None), ([0, 4], [0, -1], None)],
+        vec![Exit::new("A", [0, 2], None, None)],
+    ));
+    assert_eq!(
+        departures(&mut simulation, 3),
+        vec![
+            Event::Rescued {
+                crab: 0,
+                exit: Some("A".to_string())
+            },
+            Event::Rescued {
+                crab: 1,
+                exit: Some("A".to_string())
+            },
+        ]
+    );
+    assert_eq!(simulation.map().exit([0, 2]).unwrap().used(), &[0, 1][..]);
+}
+
+#[test]
+fn crabs_reaching_a_full_exit_are_lost() {
+    let mut simulation = Simulation::new(level(
+        " X \n###",
+        vec![([0, 0], [0, 1], None), ([0, 2], [0, -1], None)],
+        vec![Exit::new("A", [0, 1], Some(1), None)],
+    ));
+    assert_eq!(
+        departures(&mut simulation, 2),
+        vec![
+            Event::Rescued {
+                crab: 0,
+                exit: Some("A".to_string())
+            },
+            Event::Died {
+                crab: 1,
+                cause: Cause::ExitFull("A".to_string())
+            },
+        ]
+    );
+    assert_eq!(simulation.outcome(), Outcome::Lost);
+}
+
+#[test]
+fn crabs_arriving_together_fill_an_exit_in_id_order() {
+    let crabs = vec![([0, 2], [0, -1], None), ([0, 0], [0, 1], None)];
+    for seed in 0..8 {
+        let mut level = level(
+            " X \n###",
+            crabs.clone(),
+            vec![Exit::new("A", [0, 1], Some(1), None)],
+        );
+        level.rescue = 1;
+        let mut simulation = Simulation::with_seed(level, seed);
+        simulation.step();
+        assert_eq!(simulation.map().exit([0, 1]).unwrap().used(), &[0][..]);
+        assert_eq!(simulation.outcome(), Outcome::Won);
+    }
+}
+
+#[test]
+fn exit_only_counts_its_own_team() {
+    let mut simulation = Simulation::new(level(
+        "X    X\n######",
+        vec![
+            ([0, 2], [0, -1], Some(Team::Red)),
+            ([0, 3], [0, 1], Some(Team::Red)),
+        ],
+        vec![
+            Exit::new("red", [0, 0], None, Some(Team::Red)),
+            Exit::new("blue", [0, 5], None, Some(Team::Blue)),
+        ],
+    ));
+    assert_eq!(
+        departures(&mut simulation, 3),
+        vec![
+            Event::Rescued {
+                crab: 0,
+                exit: Some("red".to_string())
+            },
+            Event::Died {
+                crab: 1,
+                cause: Cause::WrongExit("blue".to_string())
+            },
+        ]
+    );
+}
+
+#[test]
+fn hatches_release_crabs_on_their_team() {
+    let mut hatch = Spawner::new([0, 0], 1, 1, [0, 1], Kind::Common);
+    hatch.team = Some(Team::Green);
+    let mut map = Map::new("H  X\n    \n####");
+    map.exits = vec![Exit::new("green", [0, 3], None, Some(Team::Green))];
+    let mut simulation = Simulation::new(Level::new(
+        Entities::new(Vec::new(), Vec::new()).with_spawners(vec![hatch]),
+        map,
+    ));
+    simulation.step();
+    assert_eq!(
+        simulation.entities().iter().next().unwrap().team(),
+        Some(Team::Green)
+    );
+}
+
+#[test]
+fn exits_and_teams_round_trip_through_level_files() {
+    let source = r#"(
+    version: 2,
+    crabs: [(pos: (0, 0), vel: (0, 1), team: Some(Blue)), (pos: (0, 1), vel: (0, 1))],
+    spawners: [(pos: (0, 2), count: 1, interval: 1, vel: (0, 1), team: Some(Red))],
+    exits: [(name: "A", pos: (1, 3), capacity: Some(3), team: Some(Blue)), (name: "B", pos: (1, 0))],
+    layout: "  H \nX  X\n####",
+)"#;
+    assert!(crabs::validate::validate_level(source).is_empty());
+    let file = LevelFile::parse(source).unwrap();
+    let level = file.clone().into_level();
+    let teams: Vec<Option<Team>> = level.entities.iter().map(|crab| crab.team()).collect();
+    assert_eq!(teams, vec![Some(Team::Blue), None]);
+    assert_eq!(
+        level.map.exits,
+        vec![
+            Exit::new("A", [1, 3], Some(3), Some(Team::Blue)),
+            Exit::new("B", [1, 0], None, None),
+        ]
+    );
+
+    let again = LevelFile::from_level(&level);
+    assert_eq!(again.crabs, file.crabs);
+    assert_eq!(again.spawners, file.spawners);
+    assert_eq!(again.exits, file.exits);
+}
+
+#[test]
+fn exits_must_be_on_distinct_safety_tiles() {
+    let source = r#"(
+    version: 2,
+    crabs: [],
+    exits: [(name: "A", pos: (0, 0)), (name: "A", pos: (0, 2)), (name: "C", pos: (0, 1))],
+    layout: "X X\n###",
+)"#;
+    let problems: Vec<String> = crabs::validate::validate_level(source)
+        .iter()
+        .map(|diagnostic| diagnostic.problem.to_string())
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            "exit \"A\" shares its name or tile with another exit",
+            "exit \"C\" at [0, 1] is not on a X safety tile",
+        ]
+    );
+}
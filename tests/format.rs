@@ -0,0 +1,60 @@
+use crabs::format::{LegacyLevel, LevelError, LevelFile};
+use crabs::validate::{validate_level, Problem};
+
+#[test]
+fn files_without_a_version_are_only_read_as_legacy_levels() {
+    let legacy = "(x: {0: 1}, y: {0: 0}, vx: {0: 1}, vy: {0: 0}, layout: \"  X\\n###\")";
+    assert_eq!(LevelFile::parse(legacy).unwrap().version, 2);
+
+    let unversioned = "(crabs: [(pos: (0, 0), vel: (0, 1))], layout: \"  X\\n###\")";
+    assert!(matches!(
+        LevelFile::parse(unversioned),
+        Err(LevelError::MissingVersion)
+    ));
+    let diagnostics = validate_level(unversioned);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].problem, Problem::MissingVersion);
+
+    // Such as a level pack manifest
+    let manifest = "(name: \"Pack\", levels: [(title: \"First\", file: \"first.ron\")])";
+    assert_eq!(validate_level(manifest)[0].problem, Problem::MissingVersion);
+}
+
+fn legacy(source: &str) -> LegacyLevel {
+    ron::de::from_str(source).unwrap()
+}
+
+#[test]
+fn legacy_crabs_are_paired_up_by_id() {
+    let file = LevelFile::migrate(legacy(
+        r#"(
+    x: {2: 5, 0: 1, 1: 3},
+    y: {1: 0, 2: 1, 0: 0},
+    vx: {1: -1, 0: 1, 2: 2},
+    vy: {2: -3, 1: 0, 0: 0},
+    layout: "      X\n       \n#######",
+)"#,
+    ))
+    .unwrap();
+    let crabs: Vec<_> = file
+        .crabs
+        .iter()
+        .map(|crab| (crab.id, crab.pos, crab.vel))
+        .collect();
+    assert_eq!(
+        crabs,
+        [
+            (Some(0), (0, 1), (0, 1)),
+            (Some(1), (0, 3), (0, -1)),
+            (Some(2), (1, 5), (-3, 2)),
+        ]
+    );
+}
+
+#[test]
+fn legacy_crabs_need_an_entry_in_every_map() {
+    let result = LevelFile::migrate(legacy(
+        "(x: {0: 1, 1: 3}, y: {0: 0, 1: 0}, vx: {0: 1}, vy: {0: 0, 1: 0}, layout: \"    X\")",
+    ));
+    assert!(matches!(result, Err(LevelError::MissingCrab(1))));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::simulation;
+use crabs::{Cause, Event, Outcome, Scenery, Simulation};
+
+// Every event over a number of ticks
+fn events(simulation: &mut Simulation, ticks: usize) -> Vec<Event> {
+    (0..ticks).flat_map(|_| simulation.step()).collect()
+}
+
+// A column to fall down, with the crab at the top
+fn drop(height: usize) -> String {
+    let mut layout = String::from("#X#\n");
+    for _ in 0..height {
+        layout.push_str("   \n");
+    }
+    layout.push_str("###");
+    layout
+}
+
+#[test]
+fn long_falls_kill_crabs() {
+    let mut simulation = simulation(&drop(40), vec![([1, 1], [0, 0])]);
+    assert!(events(&mut simulation, 20).contains(&Event::Died {
+        crab: 0,
+        cause: Cause::Fall,
+    }));
+    assert_eq!(simulation.lost(), 1);
+    assert_eq!(simulation.outcome(), Outcome::Lost);
+}
+
+#[test]
+fn short_falls_are_survived() {
+    let mut simulation = simulation(&drop(8), vec![([1, 1], [0, 0])]);
+    assert!(events(&mut simulation, 20).is_empty());
+    assert_eq!(common::crab(&simulation), ([8, 1], [0, 0]));
+}
+
+#[test]
+fn levels_can_raise_the_fall_limit() {
+    let mut level = common::level(&drop(40), vec![([1, 1], [0, 0])]);
+    level.map.physics.fall_limit = 100;
+    let mut simulation = Simulation::new(level);
+    assert!(events(&mut simulation, 20).is_empty());
+    assert_eq!(common::crab(&simulation).0, [40, 1]);
+}
+
+#[test]
+fn water_and_lava_are_deadly() {
+    for &scenery in [Scenery::Water, Scenery::Lava].iter() {
+        let layout = format!("  {} X\n#####", scenery.to_char());
+        let mut simulation = simulation(&layout, vec![([0, 0], [0, 1])]);
+        assert_eq!(
+            events(&mut simulation, 3),
+            [
+                Event::Died {
+                    crab: 0,
+                    cause: Cause::Hazard(scenery),
+                },
+                Event::Finished(Outcome::Lost),
+            ]
+        );
+    }
+}
+
+#[test]
+fn levels_are_lost_as_soon_as_the_quota_is_out_of_reach() {
+    let layout = "^         X\n###########";
+    let crabs = vec![([0, 1], [0, -1]), ([0, 2], [0, 1])];
+
+    // Needing both crabs, losing one is enough
+    let mut level = common::level(layout, crabs.clone());
+    level.rescue = 2;
+    let mut both = Simulation::new(level);
+    let tick = both.step();
+    assert!(tick.contains(&Event::Died {
+        crab: 0,
+        cause: Cause::Hazard(Scenery::Spikes),
+    }));
+    assert_eq!(tick.last(), Some(&Event::Finished(Outcome::Lost)));
+    assert_eq!(common::crabs(&both).len(), 1);
+
+    // Needing only one, play goes on
+    let mut level = common::level(layout, crabs);
+    level.rescue = 1;
+    let mut either = Simulation::new(level);
+    either.step();
+    assert_eq!(either.outcome(), Outcome::Playing);
+    events(&mut either, 10);
+    assert_eq!(either.outcome(), Outcome::Won);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::crab;
+use crabs::format::LevelFile;
+use crabs::{Cause, Continuous, Entities, Event, Kind, Level, Map, Outcome, Scenery, Simulation};
+
+fn level(layout: &str, crabs: Vec<([usize; 2], [isize; 2], Kind)>) -> Level {
+    let entities = Entities::with_kinds(
+        crabs.iter().map(|crab| crab.0).collect(),
+        crabs.iter().map(|crab| crab.1).collect(),
+        crabs.iter().map(|crab| crab.2).collect(),
+    );
+    Level::new(entities, Map::new(layout))
+}
+
+fn simulation(layout: &str, crabs: Vec<([usize; 2], [isize; 2], Kind)>) -> Simulation {
+    Simulation::new(level(layout, crabs))
+}
+
+const TRAMPOLINE: &str = "#   #\n#   #\n#   #\n# @ #\n#####";
+
+#[test]
+fn heavy_crabs_break_trampolines() {
+    let mut simulation = simulation(TRAMPOLINE, vec![([0, 2], [0, 0], Kind::Heavy)]);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crab(&simulation), ([2, 2], [0, 0]));
+    assert_eq!(
+        simulation.map().to_string(),
+        "#   #\n#   #\n# . #\n#   #\n#####"
+    );
+
+    // Nothing left to stop the crab falling through
+    simulation.step();
+    assert_eq!(crab(&simulation).0, [3, 2]);
+}
+
+#[test]
+fn common_crabs_bounce_off_trampolines() {
+    let mut simulation = simulation(TRAMPOLINE, vec![([0, 2], [0, 0], Kind::Common)]);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crab(&simulation), ([2, 2], [-10, 0]));
+    assert!(simulation.map().to_string().contains('@'));
+}
+
+#[test]
+fn climbers_scale_walls_one_block_high() {
+    let mut simulation = simulation(
+        "#       #\n#   #   #\n#########",
+        vec![([1, 1], [0, 1], Kind::Climber)],
+    );
+    for _ in 0..4 {
+        simulation.step();
+    }
+    assert_eq!(crab(&simulation).0[1], 5);
+}
+
+#[test]
+fn climbers_turn_back_from_taller_walls() {
+    let mut simulation = simulation(
+        "#   #   #\n#   #   #\n#########",
+        vec![([1, 1], [0, 1], Kind::Climber)],
+    );
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crab(&simulation), ([1, 3], [0, -1]));
+}
+
+#[test]
+fn floaters_survive_long_falls() {
+    let mut layout = String::from("#X#\n");
+    for _ in 0..40 {
+        layout.push_str("   \n");
+    }
+    layout.push_str("###");
+    let mut floater = simulation(&layout, vec![([1, 1], [0, 0], Kind::Floater)]);
+    let mut common = simulation(&layout, vec![([1, 1], [0, 0], Kind::Common)]);
+    for _ in 0..50 {
+        floater.step();
+        common.step();
+    }
+    assert_eq!(crab(&floater), ([40, 1], [0, 0]));
+    assert_eq!(common.lost(), 1);
+}
+
+#[test]
+fn floaters_drift_down_under_continuous_physics() {
+    let mut map = Map::new("   \n   \n   \n   \n###");
+    map.physics.continuous = Some(Continuous::default());
+    let entities = Entities::with_kinds(vec![[0, 1]], vec![[0, 0]], vec![Kind::Floater]);
+    let mut simulation = Simulation::new(Level::new(entities, map));
+    for _ in 0..8 {
+        simulation.step();
+        assert!(simulation.entities().iter().next().unwrap().speed()[0] <= crabs::SCALE);
+    }
+}
+
+#[test]
+fn losing_the_king_loses_the_level() {
+    let crabs = |king| vec![([0, 2], [0, -1], Kind::Common), ([0, 4], [0, 1], king)];
+    let mut with_king = simulation("X    ^\n######", crabs(Kind::King));
+    // Only one crab needs rescuing
+    let mut without_king = level("X    ^\n######", crabs(Kind::Common));
+    without_king.rescue = 1;
+    let mut without_king = Simulation::new(without_king);
+
+    let events = with_king.step();
+    assert!(events.contains(&Event::Died {
+        crab: 1,
+        cause: Cause::Hazard(Scenery::Spikes),
+    }));
+    assert_eq!(with_king.outcome(), Outcome::Lost);
+
+    without_king.step();
+    without_king.step();
+    assert_eq!(without_king.outcome(), Outcome::Won);
+}
+
+#[test]
+fn kinds_are_read_from_level_files() {
+    let file = LevelFile::parse(
+        "(version: 2, crabs: [(pos: (0, 0), vel: (0, 1)), (pos: (0, 1), vel: (0, 1), kind: Heavy)], layout: \"  X\\n###\")",
+    )
+    .unwrap();
+    let level = file.clone().into_level();
+    let kinds: Vec<Kind> = level.entities.iter().map(|crab| crab.kind()).collect();
+    assert_eq!(kinds, [Kind::Common, Kind::Heavy]);
+    assert_eq!(LevelFile::from_level(&level), file);
+    assert!(!file.to_ron().contains("Common"));
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::{crabs, glyph};
+use crabs::format::LevelFile;
+use crabs::{Continuous, Entities, Event, Level, Link, Map, Simulation};
+
+fn simulation(
+    layout: &str,
+    crabs: Vec<([usize; 2], [isize; 2])>,
+    links: Vec<([usize; 2], Vec<[usize; 2]>)>,
+) -> Simulation {
+    let mut level = common::level(layout, crabs);
+    level.map.links = links
+        .into_iter()
+        .map(|(plate, doors)| Link { plate, doors })
+        .collect();
+    Simulation::new(level)
+}
+
+fn rescued(events: &[Event]) -> bool {
+    events
+        .iter()
+        .any(|event| matches!(event, Event::Rescued { .. }))
+}
+
+#[test]
+fn shut_door_turns_crabs_back() {
+    let mut simulation = simulation("    DX\n######", vec![([0, 2], [0, 1])], Vec::new());
+    simulation.step();
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 3], [0, -1])]);
+}
+
+#[test]
+fn crab_on_a_plate_opens_linked_door() {
+    let mut simulation = simulation(
+        "    DX\n_#####",
+        vec![([0, 0], [0, 0]), ([0, 2], [0, 1])],
+        vec![([1, 0], vec![[0, 4]])],
+    );
+    assert!(simulation.map().is_pressed([1, 0]));
+    simulation.step();
+    assert_eq!(glyph(simulation.map(), [0, 4]), 'd');
+    simulation.step();
+    assert_eq!(crabs(&simulation)[1], ([0, 4], [0, 1]));
+    assert!(rescued(&simulation.step()));
+}
+
+#[test]
+fn door_shuts_again_once_the_crab_steps_off() {
+    let mut simulation = simulation(
+        "     D\n#_####",
+        vec![([0, 0], [0, 1])],
+        vec![([1, 1], vec![[0, 5]])],
+    );
+    simulation.step();
+    assert_eq!(glyph(simulation.map(), [0, 5]), 'd');
+    simulation.step();
+    assert_eq!(glyph(simulation.map(), [0, 5]), 'D');
+}
+
+#[test]
+fn door_does_not_shut_on_a_crab() {
+    let mut simulation = simulation(
+        " d\n_#",
+        vec![([0, 0], [0, 0]), ([0, 1], [0, 0])],
+        vec![([1, 0], vec![[0, 1]])],
+    );
+    let events = simulation.step();
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, Event::Embedded { .. })));
+    assert_eq!(glyph(simulation.map(), [0, 1]), 'd');
+}
+
+#[test]
+fn conveyor_carries_a_standing_crab() {
+    let mut simulation = simulation("    \n}}}}", vec![([0, 0], [0, 0])], Vec::new());
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 1], [0, 0])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 2], [0, 0])]);
+}
+
+#[test]
+fn conveyor_adds_to_a_walking_crab() {
+    let mut simulation = simulation("      \n{{{{{{", vec![([0, 5], [0, -1])], Vec::new());
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 3], [0, -1])]);
+}
+
+#[test]
+fn conveyor_carries_crabs_under_continuous_physics() {
+    let mut map = Map::new("    \n}}}}");
+    map.physics.continuous = Some(Continuous::default());
+    let mut simulation =
+        Simulation::new(Level::new(Entities::new(vec![[0, 0]], vec![[0, 0]]), map));
+    simulation.step();
+    assert_eq!(
+        simulation.entities().iter().next().unwrap().position(),
+        [0, 1]
+    );
+}
+
+#[test]
+fn conveyor_carries_crabs_to_safety() {
+    let mut simulation = simulation("  X\n}}}", vec![([0, 1], [0, 0])], Vec::new());
+    assert!(rescued(&simulation.step()));
+}
+
+#[test]
+fn gates_only_let_crabs_through_one_way() {
+    let mut forward = simulation("  )  \n#####", vec![([0, 0], [0, 1])], Vec::new());
+    for _ in 0..3 {
+        forward.step();
+    }
+    assert_eq!(crabs(&forward), vec![([0, 3], [0, 1])]);
+
+    let mut backward = simulation("  )  \n#####", vec![([0, 4], [0, -1])], Vec::new());
+    backward.step();
+    backward.step();
+    assert_eq!(crabs(&backward), vec![([0, 3], [0, 1])]);
+}
+
+#[test]
+fn links_round_trip_through_level_files() {
+    let source = r#"(
+    version: 2,
+    crabs: [(pos: (0, 0), vel: (0, 1))],
+    links: [(plate: (1, 0), doors: [(0, 3), (0, 4)])],
+    layout: "   DdX\n_}{)(#",
+)"#;
+    assert!(crabs::validate::validate_level(source).is_empty());
+    let file = LevelFile::parse(source).unwrap();
+    let level = file.clone().into_level();
+    assert_eq!(
+        level.map.links,
+        vec![Link {
+            plate: [1, 0],
+            doors: vec![[0, 3], [0, 4]]
+        }]
+    );
+    let again = LevelFile::from_level(&level);
+    assert_eq!(again.links, file.links);
+    assert_eq!(again.layout, file.layout);
+}
+
+#[test]
+fn links_must_join_plates_to_doors() {
+    let source = r#"(
+    version: 2,
+    crabs: [],
+    links: [(plate: (0, 1), doors: [(1, 0), (0, 0)])],
+    layout: "D X\n_##",
+)"#;
+    let problems: Vec<String> = crabs::validate::validate_level(source)
+        .iter()
+        .map(|diagnostic| diagnostic.problem.to_string())
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            "link 0 expects a _ tile at [0, 1]",
+            "link 0 expects a D tile at [1, 0]",
+        ]
+    );
+}
@@ -0,0 +1,92 @@
+use std::env;
+use std::fs;
+
+use crabs::format::load_level;
+use crabs::pack::PackError;
+use crabs::Pack;
+
+#[test]
+fn packs_need_at_least_one_level() {
+    let directory = env::temp_dir().join(format!("crabs-pack-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let empty = Pack::load(&directory);
+
+    let manifest = directory.join("empty.ron");
+    fs::write(&manifest, "(name: \"Nothing\", levels: [])").unwrap();
+    let unlisted = Pack::load(&manifest);
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(matches!(empty, Err(PackError::Empty(_))));
+    assert!(matches!(unlisted, Err(PackError::Empty(path)) if path == manifest));
+}
+
+#[test]
+fn levels_in_a_pack_need_their_own_titles() {
+    let directory = env::temp_dir().join(format!("crabs-titles-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::copy("examples/level_1.ron", directory.join("level.ron")).unwrap();
+    let manifest = directory.join("pack.ron");
+    fs::write(
+        &manifest,
+        "(name: \"Twice\", levels: [(title: \"Same\", file: \"level.ron\"), \
+         (title: \"Same\", file: \"level.ron\")])",
+    )
+    .unwrap();
+    let pack = Pack::load(&directory);
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(matches!(pack, Err(PackError::DuplicateTitle(path, title))
+        if path == manifest && title == "Same"));
+}
+
+// Names of the pack and each of its levels
+fn titles(pack: &Pack) -> (&str, Vec<&str>) {
+    (
+        &pack.name,
+        pack.levels
+            .iter()
+            .map(|level| level.name.as_str())
+            .collect(),
+    )
+}
+
+#[test]
+fn manifests_set_the_order_and_titles() {
+    let pack = Pack::load("examples/pack.ron").unwrap();
+    assert_eq!(
+        titles(&pack),
+        ("Examples", vec!["A tiny level", "The long walk"])
+    );
+    let level = load_level("examples/level_1.ron").unwrap();
+    assert_eq!(pack.levels[1].map.to_string(), level.map.to_string());
+}
+
+#[test]
+fn directories_are_played_in_file_name_order_unless_they_have_a_manifest() {
+    let directory = env::temp_dir().join(format!("crabs-directory-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::copy("examples/custom_level.ron", directory.join("b.ron")).unwrap();
+    fs::copy("examples/level_1.ron", directory.join("a.ron")).unwrap();
+    fs::write(directory.join("notes.txt"), "Not a level").unwrap();
+    let listed = Pack::load(&directory);
+
+    fs::write(
+        directory.join("pack.ron"),
+        "(name: \"Backwards\", levels: [(title: \"Bee\", file: \"b.ron\"), \
+         (title: \"Ay\", file: \"a.ron\")])",
+    )
+    .unwrap();
+    let manifest = Pack::load(&directory);
+    fs::remove_dir_all(&directory).unwrap();
+
+    let listed = listed.unwrap();
+    let name = directory.file_name().unwrap().to_str().unwrap();
+    assert_eq!(titles(&listed), (name, vec!["Level 1", "Custom level"]));
+    assert_eq!(titles(&manifest.unwrap()), ("Backwards", vec!["Bee", "Ay"]));
+}
+
+#[test]
+fn a_single_level_is_a_pack_of_one() {
+    let pack = Pack::load("examples/custom_level.ron").unwrap();
+    assert_eq!(titles(&pack), ("Custom level", vec!["Custom level"]));
+}
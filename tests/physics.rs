@@ -0,0 +1,110 @@
+mod common;
+
+use crabs::format::LevelFile;
+use crabs::{Continuous, Simulation, SCALE};
+
+fn simulation(
+    layout: &str,
+    position: [usize; 2],
+    velocity: [isize; 2],
+    rules: Continuous,
+) -> Simulation {
+    let mut level = common::level(layout, vec![(position, velocity)]);
+    level.map.physics.continuous = Some(rules);
+    Simulation::new(level)
+}
+
+// Speed rather than velocity, to see the fractions of a cell
+fn crab(simulation: &Simulation) -> ([usize; 2], [isize; 2]) {
+    let crab = simulation.entities().iter().next().unwrap();
+    (crab.position(), crab.speed())
+}
+
+#[test]
+fn crabs_walking_off_a_ledge_fall_in_an_arc() {
+    let mut simulation = simulation(
+        "#     \n###   \n#     \n#     \n######",
+        [0, 1],
+        [0, 1],
+        Continuous::default(),
+    );
+    let mut cells = Vec::new();
+    for _ in 0..5 {
+        simulation.step();
+        cells.push(crab(&simulation).0);
+    }
+    // Moving across and down in the same tick, rather than a cell at a time
+    assert_eq!(cells, [[0, 2], [1, 3], [1, 4], [2, 5], [3, 5]]);
+}
+
+#[test]
+fn trampolines_bounce_crabs_back_to_where_they_fell_from() {
+    let mut simulation = simulation(
+        "#     #\n#     #\n#     #\n#     #\n#  @  #\n#######",
+        [0, 3],
+        [0, 0],
+        Continuous::default(),
+    );
+    let mut highest = 4;
+    for tick in 0..20 {
+        simulation.step();
+        if tick > 5 {
+            highest = highest.min(crab(&simulation).0[0]);
+        }
+    }
+    assert_eq!(highest, 1);
+}
+
+#[test]
+fn bounces_die_away_with_restitution() {
+    let rules = Continuous {
+        restitution: SCALE / 2,
+        ..Continuous::default()
+    };
+    let mut simulation = simulation("#   #\n#   #\n#   #\n#####", [0, 2], [0, 0], rules);
+    let mut bounced = false;
+    for _ in 0..12 {
+        simulation.step();
+        bounced |= crab(&simulation).1[0] < 0;
+    }
+    assert!(bounced);
+    assert_eq!(crab(&simulation), ([2, 2], [0, 0]));
+}
+
+#[test]
+fn friction_slows_crabs_down_on_the_ground() {
+    let rules = Continuous {
+        friction: SCALE / 4,
+        ..Continuous::default()
+    };
+    let mut simulation = simulation(
+        "#                    #\n######################",
+        [0, 1],
+        [0, 3],
+        rules,
+    );
+    let mut speeds = Vec::new();
+    for _ in 0..4 {
+        simulation.step();
+        speeds.push(crab(&simulation).1[1]);
+    }
+    assert_eq!(speeds, [576, 432, 324, 243]);
+}
+
+#[test]
+fn physics_settings_are_read_from_level_files() {
+    let file = LevelFile::parse(
+        "(version: 2, physics: Some((gravity: 0.5, restitution: 0.25)), crabs: [(pos: (0, 1), vel: (0, 1))], layout: \" X\\n##\")",
+    )
+    .unwrap();
+    let level = file.clone().into_level();
+    assert_eq!(
+        level.map.physics.continuous,
+        Some(Continuous {
+            gravity: SCALE / 2,
+            friction: 0,
+            restitution: SCALE / 4,
+        })
+    );
+    assert_eq!(LevelFile::from_level(&level).physics, file.physics);
+}
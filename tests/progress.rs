@@ -0,0 +1,71 @@
+mod common;
+
+use crabs::progress::{Progress, Record};
+use crabs::Pack;
+
+// A pack of levels with the given titles
+fn pack(titles: &[&str]) -> Pack {
+    Pack {
+        name: String::from("Tests"),
+        levels: titles
+            .iter()
+            .map(|title| {
+                let mut level = common::level("  X\n###", vec![([0, 0], [0, 1])]);
+                level.name = title.to_string();
+                level
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn completing_a_level_keeps_the_best_scores() {
+    let pack = pack(&["First"]);
+    let mut progress = Progress::default();
+    progress.complete(&pack, 0, 3, 40);
+    progress.complete(&pack, 0, 5, 20);
+    progress.complete(&pack, 0, 4, 30);
+    assert_eq!(
+        progress.record(&pack, 0),
+        Some(&Record {
+            completed: true,
+            unlocked: true,
+            best_placements: Some(3),
+            best_ticks: Some(20),
+        })
+    );
+}
+
+#[test]
+fn completing_a_level_unlocks_the_next() {
+    let pack = pack(&["First", "Second", "Third"]);
+    let mut progress = Progress::default();
+    assert!(progress.is_unlocked(&pack, 0));
+    assert!(!progress.is_unlocked(&pack, 1));
+    assert!(!progress.is_completed(&pack, 0));
+
+    progress.complete(&pack, 0, 1, 10);
+    assert!(progress.is_completed(&pack, 0));
+    assert!(progress.is_unlocked(&pack, 1));
+    assert!(!progress.is_completed(&pack, 1));
+    assert!(!progress.is_unlocked(&pack, 2));
+
+    // Nothing is unlocked past the end of the pack
+    progress.complete(&pack, 2, 1, 10);
+    let mut longer = pack.clone();
+    longer.levels.push(longer.levels[0].clone());
+    longer.levels[3].name = String::from("Fourth");
+    assert!(!progress.is_unlocked(&longer, 3));
+}
+
+#[test]
+fn progress_survives_being_saved() {
+    let pack = pack(&["First", "Second"]);
+    let mut progress = Progress::default();
+    progress.complete(&pack, 0, 2, 15);
+
+    let source = ron::ser::to_string(&progress).unwrap();
+    let loaded: Progress = ron::de::from_str(&source).unwrap();
+    assert_eq!(loaded, progress);
+    assert!(loaded.is_unlocked(&pack, 1));
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+mod common;
+
+use crabs::solve::{self, Budget, Verdict};
+use crabs::{Edit, Event, Origin, Refusal, Scenery, Simulation, Skill, Skills};
+
+fn simulation(layout: &str, crabs: Vec<([usize; 2], [isize; 2])>) -> Simulation {
+    let mut level = common::level(layout, crabs);
+    level.skills = Skills::new(HashMap::from([(Skill::Digger, 1)]));
+    Simulation::new(level)
+}
+
+fn place(simulation: &mut Simulation, position: [usize; 2], scenery: Scenery) -> Event {
+    simulation.apply_edit(Edit::Place { position, scenery })
+}
+
+#[test]
+fn placed_scenery_is_told_apart_from_the_level() {
+    let mut simulation = simulation("     \n#####", Vec::new());
+    assert_eq!(simulation.map().origin([1, 0]), Origin::Level);
+    assert_eq!(simulation.map().origin([0, 0]), Origin::Level);
+
+    place(&mut simulation, [0, 3], Scenery::ForwardWedge);
+    place(&mut simulation, [0, 1], Scenery::Block);
+    assert_eq!(simulation.map().origin([0, 3]), Origin::Player);
+    assert_eq!(
+        simulation.map().placed(),
+        [([0, 1], Scenery::Block), ([0, 3], Scenery::ForwardWedge)]
+    );
+
+    // Once taken away, the cell goes back to how the level was
+    simulation.apply_edit(Edit::Remove { position: [0, 1] });
+    assert_eq!(simulation.map().origin([0, 1]), Origin::Level);
+    assert_eq!(simulation.map().placed(), [([0, 3], Scenery::ForwardWedge)]);
+}
+
+#[test]
+fn scenery_the_crabs_change_is_theirs() {
+    let mut simulation = simulation("#   #\n#   #\n#####", vec![([0, 2], [0, 0])]);
+    place(&mut simulation, [1, 2], Scenery::Block);
+    simulation.apply_edit(Edit::Assign {
+        position: [0, 2],
+        skill: Skill::Digger,
+    });
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(simulation.map().origin([1, 2]), Origin::Crab);
+    assert!(simulation.map().placed().is_empty());
+
+    // So the player can no longer take it back
+    assert!(matches!(
+        simulation.apply_edit(Edit::Remove { position: [1, 2] }),
+        Event::Rejected {
+            reason: Refusal::NotPlaced,
+            ..
+        }
+    ));
+    assert!(matches!(
+        simulation.apply_edit(Edit::Undo),
+        Event::Rejected { .. }
+    ));
+    assert_eq!(simulation.apply_edit(Edit::Undo), Event::Unchanged);
+    assert_eq!(simulation.placements(), 1);
+}
+
+#[test]
+fn solutions_mark_the_scenery_they_add() {
+    let level = common::level("   #X\n#####", vec![([0, 0], [0, 1])]);
+    match solve::solve(&level, &Budget::default()) {
+        Verdict::Solved(solution) => {
+            assert_eq!(solution.map.placed(), solution.placements);
+        }
+        verdict => panic!("expected a solution, got {:?}", verdict),
+    }
+}
@@ -0,0 +1,93 @@
+use std::env;
+use std::fs;
+
+use termion::event::Key;
+
+mod common;
+
+use crabs::replay::{Playback, Replay, ReplayError, Session};
+use crabs::{Outcome, TICK_TIME};
+
+// The crab walks into the spikes unless it's turned back by a block
+const LEVEL: &str = "^     X\n#######";
+
+// Play the level badly, restart, then play it well, recording every key as the game does
+fn record(seed: Option<u64>) -> (Replay, Session) {
+    let level = common::level(LEVEL, vec![([0, 3], [0, -1])]);
+    let mut replay = Replay::new("Tests", 0, &level, TICK_TIME, seed);
+    let mut session = Session::new(level, seed);
+    for _ in 0..3 {
+        session.step();
+    }
+    assert_eq!(session.simulation().outcome(), Outcome::Lost);
+
+    for &key in [Key::Char('r'), Key::Up, Key::Left, Key::Char('#')].iter() {
+        replay.record(session.simulation().tick(), key);
+        session.press(key);
+    }
+    for _ in 0..5 {
+        session.step();
+    }
+    assert_eq!(session.simulation().outcome(), Outcome::Won);
+    (replay, session)
+}
+
+#[test]
+fn replays_are_saved_and_loaded() {
+    let (replay, _) = record(Some(7));
+    let path = env::temp_dir().join(format!("crabs-replay-{}.ron", std::process::id()));
+    let path = path.to_str().unwrap();
+    replay.save(path).unwrap();
+    let loaded = Replay::load(path);
+    fs::remove_file(path).unwrap();
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded, replay);
+    assert_eq!(loaded.inputs.len(), 4);
+    assert_eq!(loaded.level().map.to_string(), LEVEL);
+}
+
+#[test]
+fn replays_of_broken_levels_are_refused() {
+    let (mut replay, _) = record(None);
+    replay.map.crabs[0].pos = (9, 9);
+    let path = env::temp_dir().join(format!("crabs-broken-{}.ron", std::process::id()));
+    let path = path.to_str().unwrap();
+    replay.save(path).unwrap();
+    let loaded = Replay::load(path);
+    fs::remove_file(path).unwrap();
+
+    match loaded {
+        Err(ReplayError::Invalid(diagnostics)) => assert_eq!(diagnostics.len(), 1),
+        other => panic!("expected the level to be refused, got {:?}", other),
+    }
+}
+
+#[test]
+fn later_levels_are_recorded_to_numbered_files() {
+    assert_eq!(Replay::numbered("run.ron", 1), "run.ron");
+    assert_eq!(Replay::numbered("dir/run.ron", 3), "dir/run-3.ron");
+    assert_eq!(Replay::numbered("run", 2), "run-2");
+}
+
+#[test]
+fn playback_ends_where_the_recording_did() {
+    for &seed in [None, Some(7)].iter() {
+        let (replay, live) = record(seed);
+        let mut playback = Playback::new(&replay);
+
+        // Losing the first attempt doesn't end the playback, as it was restarted
+        let mut ticks = 0;
+        while !playback.is_finished() && ticks < 20 {
+            playback.step();
+            ticks += 1;
+        }
+        let (played, live) = (playback.session().simulation(), live.simulation());
+        assert_eq!(ticks, 3 + live.tick());
+        assert_eq!(played.outcome(), Outcome::Won);
+        assert_eq!(played.tick(), live.tick());
+        assert_eq!(played.rescued(), live.rescued());
+        assert_eq!(played.placements(), live.placements());
+        assert_eq!(played.map().to_string(), live.map().to_string());
+    }
+}
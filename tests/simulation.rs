@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+mod common;
+
+use common::{crabs, simulation};
+use crabs::{Edit, Event, Inventory, Outcome, Refusal, Scenery, Simulation};
+
+#[test]
+fn each_step_moves_crabs_on_a_tick() {
+    let mut simulation = simulation("     X\n######", vec![([0, 0], [0, 1])]);
+    assert_eq!(simulation.tick(), 0);
+    assert!(simulation.step().is_empty());
+    assert!(simulation.step().is_empty());
+    assert_eq!(simulation.tick(), 2);
+    assert_eq!(crabs(&simulation), [([0, 2], [0, 1])]);
+    assert_eq!(simulation.map().to_string(), "  .  X\n######");
+}
+
+#[test]
+fn levels_are_won_once_every_crab_is_safe() {
+    let mut simulation = simulation("  X\n###", vec![([0, 0], [0, 1])]);
+    assert_eq!(simulation.outcome(), Outcome::Playing);
+    simulation.step();
+    assert_eq!(
+        simulation.step(),
+        [
+            Event::Rescued {
+                crab: 0,
+                exit: None
+            },
+            Event::Finished(Outcome::Won),
+        ]
+    );
+    assert_eq!(simulation.outcome(), Outcome::Won);
+    assert_eq!(simulation.rescued(), 1);
+
+    // Nothing more happens once the level is over
+    assert!(simulation.step().is_empty());
+    assert_eq!(simulation.tick(), 2);
+    simulation.quit();
+    assert_eq!(simulation.outcome(), Outcome::Won);
+}
+
+#[test]
+fn quitting_abandons_the_level() {
+    let mut simulation = simulation("  X\n###", vec![([0, 0], [0, 1])]);
+    simulation.quit();
+    assert_eq!(simulation.outcome(), Outcome::Quit);
+    assert!(simulation.step().is_empty());
+    assert_eq!(crabs(&simulation), [([0, 0], [0, 1])]);
+}
+
+#[test]
+fn edits_place_and_remove_scenery() {
+    let mut simulation = simulation("    X\n#####", Vec::new());
+    let position = [0, 1];
+    assert_eq!(
+        simulation.apply_edit(Edit::Place {
+            position,
+            scenery: Scenery::Block,
+        }),
+        Event::Placed {
+            position,
+            scenery: Scenery::Block,
+        }
+    );
+    assert_eq!(simulation.placements(), 1);
+    assert_eq!(
+        simulation.apply_edit(Edit::Place {
+            position,
+            scenery: Scenery::Trampoline,
+        }),
+        Event::Rejected {
+            position,
+            scenery: Scenery::Trampoline,
+            reason: Refusal::Occupied,
+        }
+    );
+
+    assert_eq!(
+        simulation.apply_edit(Edit::Remove { position }),
+        Event::Removed {
+            position,
+            scenery: Scenery::Block,
+        }
+    );
+    assert_eq!(simulation.placements(), 0);
+    assert_eq!(simulation.map().to_string(), "    X\n#####");
+}
+
+#[test]
+fn placements_are_limited_by_the_inventory() {
+    let mut level = common::level("    X\n#####", Vec::new());
+    level.map.inventory = Inventory::limited(HashMap::from([(Scenery::Block, 1)]));
+    let mut simulation = Simulation::new(level);
+    let place = |position, scenery| Edit::Place { position, scenery };
+
+    assert_eq!(
+        simulation.apply_edit(place([0, 0], Scenery::Block)),
+        Event::Placed {
+            position: [0, 0],
+            scenery: Scenery::Block,
+        }
+    );
+    // Past the budget for a tile, or a tile that isn't in the inventory at all
+    for &scenery in [Scenery::Block, Scenery::Trampoline].iter() {
+        assert_eq!(
+            simulation.apply_edit(place([0, 1], scenery)),
+            Event::Rejected {
+                position: [0, 1],
+                scenery,
+                reason: Refusal::OutOfStock,
+            }
+        );
+    }
+    assert_eq!(simulation.placements(), 1);
+    assert_eq!(
+        simulation.map().inventory.remaining(Scenery::Block),
+        Some(0)
+    );
+    assert_eq!(simulation.map().to_string(), "#   X\n#####");
+}
+
+#[test]
+fn crabs_overlapping_something_are_reported_as_embedded() {
+    let mut simulation = simulation(
+        "     X\n  #   \n######",
+        vec![([1, 2], [0, 0]), ([1, 4], [0, 0]), ([1, 4], [0, 0])],
+    );
+    let events = simulation.step();
+    assert!(events.contains(&Event::Embedded { crab: 0 }));
+    assert!(!events.contains(&Event::Embedded { crab: 1 }));
+    assert!(events.contains(&Event::Embedded { crab: 2 }));
+}
+
+#[test]
+fn edits_off_the_map_are_rejected() {
+    let mut simulation = simulation("X \n##", Vec::new());
+    let position = [5, 5];
+    assert_eq!(
+        simulation.apply_edit(Edit::Place {
+            position,
+            scenery: Scenery::Block,
+        }),
+        Event::Rejected {
+            position,
+            scenery: Scenery::Block,
+            reason: Refusal::OutOfBounds,
+        }
+    );
+    assert_eq!(
+        simulation.apply_edit(Edit::Remove { position: [0, 2] }),
+        Event::Rejected {
+            position: [0, 2],
+            scenery: Scenery::Empty,
+            reason: Refusal::OutOfBounds,
+        }
+    );
+    assert_eq!(simulation.apply_edit(Edit::Undo), Event::Unchanged);
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+mod common;
+
+use common::crabs;
+use crabs::format::LevelFile;
+use crabs::{user_input, Action, Edit, Event, Map, Refusal, Simulation, Skill, Skills};
+use termion::event::Key;
+
+fn simulation(
+    layout: &str,
+    crabs: Vec<([usize; 2], [isize; 2])>,
+    skills: &[(Skill, usize)],
+) -> Simulation {
+    let mut level = common::level(layout, crabs);
+    level.skills = Skills::new(skills.iter().copied().collect::<HashMap<_, _>>());
+    Simulation::new(level)
+}
+
+fn assign(simulation: &mut Simulation, position: [usize; 2], skill: Skill) -> Event {
+    simulation.apply_edit(Edit::Assign { position, skill })
+}
+
+#[test]
+fn skills_are_assigned_with_number_keys() {
+    let map = Map::new("   \n   ");
+    let mut cursor = [1, 2];
+    for (key, skill) in ['1', '2', '3', '4'].iter().zip(Skill::ALL.iter()) {
+        assert_eq!(
+            user_input(Key::Char(*key), &mut cursor, &map),
+            Some(Action::Edit(Edit::Assign {
+                position: [1, 2],
+                skill: *skill,
+            }))
+        );
+    }
+}
+
+#[test]
+fn skills_come_out_of_the_budget() {
+    let mut simulation = simulation(
+        "     \n#####",
+        vec![([0, 1], [0, 1]), ([0, 3], [0, -1])],
+        &[(Skill::TurnAround, 1)],
+    );
+    assert_eq!(
+        assign(&mut simulation, [0, 2], Skill::TurnAround),
+        Event::Declined {
+            position: [0, 2],
+            skill: Skill::TurnAround,
+            reason: Refusal::NoCrab,
+        }
+    );
+    assert_eq!(
+        assign(&mut simulation, [0, 1], Skill::TurnAround),
+        Event::Assigned {
+            crab: 0,
+            skill: Skill::TurnAround,
+        }
+    );
+    assert_eq!(simulation.skills().remaining(Skill::TurnAround), 0);
+    assert_eq!(crabs(&simulation)[0], ([0, 1], [0, -1]));
+    assert_eq!(
+        assign(&mut simulation, [0, 3], Skill::TurnAround),
+        Event::Declined {
+            position: [0, 3],
+            skill: Skill::TurnAround,
+            reason: Refusal::OutOfStock,
+        }
+    );
+}
+
+#[test]
+fn crabs_do_one_job_at_a_time() {
+    let mut simulation = simulation(
+        "     \n#####",
+        vec![([0, 1], [0, 1])],
+        &[(Skill::Blocker, 1), (Skill::Digger, 1)],
+    );
+    assign(&mut simulation, [0, 1], Skill::Blocker);
+    assert_eq!(
+        assign(&mut simulation, [0, 1], Skill::Digger),
+        Event::Declined {
+            position: [0, 1],
+            skill: Skill::Digger,
+            reason: Refusal::Busy,
+        }
+    );
+    assert_eq!(simulation.skills().remaining(Skill::Digger), 1);
+}
+
+#[test]
+fn blockers_turn_other_crabs_back() {
+    let mut simulation = simulation(
+        "#       #\n#########",
+        vec![([0, 4], [0, 1]), ([0, 1], [0, 1])],
+        &[(Skill::Blocker, 1)],
+    );
+    assign(&mut simulation, [0, 4], Skill::Blocker);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crabs(&simulation), [([0, 4], [0, 0]), ([0, 3], [0, -1])]);
+    for _ in 0..2 {
+        simulation.step();
+    }
+    assert_eq!(crabs(&simulation), [([0, 4], [0, 0]), ([0, 1], [0, -1])]);
+}
+
+#[test]
+fn diggers_dig_down_through_blocks() {
+    let mut simulation = simulation(
+        "#     #\n#######\n#######\n#     #\n##/####",
+        vec![([0, 2], [0, 1])],
+        &[(Skill::Digger, 1)],
+    );
+    assign(&mut simulation, [0, 2], Skill::Digger);
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crabs(&simulation), [([1, 2], [0, 0])]);
+    assert_eq!(
+        simulation.map().to_string(),
+        "#     #\n##.####\n## ####\n#     #\n##/####"
+    );
+
+    // Once through the blocks, the crab falls and carries on its way
+    for _ in 0..3 {
+        simulation.step();
+    }
+    assert_eq!(crabs(&simulation), [([3, 3], [0, 1])]);
+}
+
+#[test]
+fn builders_lay_a_staircase() {
+    let mut simulation = simulation(
+        "#          #\n#          #\n#          #\n############",
+        vec![([2, 1], [0, 1])],
+        &[(Skill::Builder, 1)],
+    );
+    assign(&mut simulation, [2, 1], Skill::Builder);
+    for _ in 0..4 {
+        simulation.step();
+    }
+    assert_eq!(
+        simulation.map().to_string(),
+        "#  .       #\n#  /       #\n# /        #\n############"
+    );
+}
+
+#[test]
+fn builders_stop_at_walls() {
+    let mut simulation = simulation(
+        "#   #\n#   #\n#####",
+        vec![([1, 2], [0, 1])],
+        &[(Skill::Builder, 1)],
+    );
+    assign(&mut simulation, [1, 2], Skill::Builder);
+    for _ in 0..4 {
+        simulation.step();
+    }
+    assert_eq!(simulation.map().to_string(), "#   #\n# ./#\n#####");
+    assert_eq!(crabs(&simulation), [([1, 2], [1, -1])]);
+}
+
+#[test]
+fn skills_are_read_from_level_files() {
+    let file = LevelFile::parse(
+        "(version: 2, skills: Some({ Blocker: 1, Builder: 2 }), crabs: [(pos: (0, 0), vel: (0, 1))], layout: \" X\\n##\")",
+    )
+    .unwrap();
+    let level = file.clone().into_level();
+    assert_eq!(
+        level.skills.counts(),
+        [(Skill::Blocker, 1), (Skill::Builder, 2)]
+    );
+    assert_eq!(LevelFile::from_level(&level), file);
+}
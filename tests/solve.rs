@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+mod common;
+
+use crabs::solve::{self, Budget, Verdict};
+use crabs::{Edit, Inventory, Level, Outcome, Scenery, Simulation, Skill, Skills};
+
+// The crab walks into the spikes unless something turns it around
+fn level() -> Level {
+    common::level("^    X\n######", vec![([0, 3], [0, -1])])
+}
+
+fn budget(placements: usize, attempts: usize) -> Budget {
+    Budget {
+        placements,
+        attempts,
+        ..Budget::default()
+    }
+}
+
+#[test]
+fn solutions_use_as_few_placements_as_possible() {
+    let winnable = common::level("     X\n######", vec![([0, 3], [0, 1])]);
+    match solve::solve(&winnable, &Budget::default()) {
+        Verdict::Solved(solution) => assert!(solution.placements.is_empty()),
+        verdict => panic!("expected a solution, got {:?}", verdict),
+    }
+
+    let solution = match solve::solve(&level(), &Budget::default()) {
+        Verdict::Solved(solution) => solution,
+        verdict => panic!("expected a solution, got {:?}", verdict),
+    };
+    assert_eq!(solution.placements.len(), 1);
+
+    // And the solution really does win
+    let mut simulation = Simulation::new(level());
+    for &(position, scenery) in solution.placements.iter() {
+        simulation.apply_edit(Edit::Place { position, scenery });
+    }
+    for _ in 0..solution.ticks {
+        simulation.step();
+    }
+    assert_eq!(simulation.outcome(), Outcome::Won);
+}
+
+#[test]
+fn levels_are_unsolvable_when_nothing_within_budget_wins() {
+    assert!(matches!(
+        solve::solve(&level(), &budget(0, 100)),
+        Verdict::Unsolvable
+    ));
+
+    // Nothing to place at all
+    let mut level = level();
+    level.map.inventory = Inventory::limited(HashMap::new());
+    assert!(matches!(
+        solve::solve(&level, &Budget::default()),
+        Verdict::Unsolvable
+    ));
+
+    // Nothing that helps
+    level.map.inventory = Inventory::limited(HashMap::from([(Scenery::BackwardBoost, 2)]));
+    assert!(matches!(
+        solve::solve(&level, &Budget::default()),
+        Verdict::Unsolvable
+    ));
+}
+
+#[test]
+fn the_solver_gives_up_when_out_of_attempts() {
+    assert!(matches!(
+        solve::solve(&level(), &budget(3, 1)),
+        Verdict::GaveUp
+    ));
+}
+
+#[test]
+fn levels_with_skills_are_not_called_unsolvable() {
+    let mut level = level();
+    level.skills = Skills::new(HashMap::from([(Skill::TurnAround, 1)]));
+    assert!(matches!(
+        solve::solve(&level, &budget(0, 100)),
+        Verdict::SkillsUntried
+    ));
+
+    // They can still be solved with placements alone
+    assert!(matches!(
+        solve::solve(&level, &Budget::default()),
+        Verdict::Solved(_)
+    ));
+}
@@ -0,0 +1,166 @@
+use crabs::format::LevelFile;
+use crabs::{Entities, Event, Kind, Level, Map, Outcome, Simulation, Spawner};
+
+fn simulation(layout: &str, spawners: Vec<Spawner>) -> Simulation {
+    Simulation::new(Level::new(
+        Entities::new(Vec::new(), Vec::new()).with_spawners(spawners),
+        Map::new(layout),
+    ))
+}
+
+fn released(events: &[Event]) -> Vec<usize> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Released { crab } => Some(*crab),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn hatch_releases_crabs_at_its_interval() {
+    let mut simulation = simulation(
+        "H    \n     \n#####",
+        vec![Spawner::new([0, 0], 3, 2, [0, 1], Kind::Common)],
+    );
+    assert_eq!(simulation.remaining(), 3);
+
+    let mut ticks = Vec::new();
+    for tick in 0..6 {
+        if !released(&simulation.step()).is_empty() {
+            ticks.push(tick);
+        }
+    }
+    assert_eq!(ticks, vec![0, 2, 4]);
+    assert_eq!(simulation.released(), 3);
+    assert_eq!(simulation.entities().pending(), 0);
+}
+
+#[test]
+fn released_crab_drops_beneath_the_hatch_with_its_velocity() {
+    let mut simulation = simulation(
+        " H   \n     \n#####",
+        vec![Spawner::new([0, 1], 1, 1, [0, 1], Kind::Common)],
+    );
+    let events = simulation.step();
+    assert_eq!(released(&events), vec![0]);
+    let crab = simulation.entities().iter().next().unwrap();
+    assert_eq!(crab.velocity(), [0, 1]);
+    assert_eq!(crab.position(), [1, 2]);
+}
+
+#[test]
+fn blocked_hatch_waits() {
+    let mut simulation = simulation(
+        "H \n# \n##",
+        vec![Spawner::new([0, 0], 2, 1, [0, 1], Kind::Common)],
+    );
+    for _ in 0..5 {
+        assert!(released(&simulation.step()).is_empty());
+    }
+    assert_eq!(simulation.entities().pending(), 2);
+    assert_eq!(simulation.outcome(), Outcome::Playing);
+}
+
+#[test]
+fn level_is_not_won_while_crabs_are_pending() {
+    let mut simulation = simulation(
+        "H  \n  X\n###",
+        vec![Spawner::new([0, 0], 2, 4, [0, 1], Kind::Common)],
+    );
+    let mut rescued_before_won = 0;
+    while simulation.outcome() == Outcome::Playing && simulation.tick() < 20 {
+        simulation.step();
+        if simulation.outcome() == Outcome::Playing {
+            rescued_before_won = simulation.rescued();
+        }
+    }
+    assert_eq!(simulation.outcome(), Outcome::Won);
+    assert_eq!(rescued_before_won, 1);
+    assert_eq!(simulation.rescued(), 2);
+}
+
+#[test]
+fn pending_crabs_count_towards_the_quota() {
+    let mut simulation = simulation(
+        "H  \n  ^\n###",
+        vec![Spawner::new([0, 0], 2, 10, [0, 1], Kind::Common)],
+    );
+    assert_eq!(simulation.quota(), 2);
+    let mut outcome_tick = None;
+    while simulation.outcome() == Outcome::Playing && simulation.tick() < 30 {
+        simulation.step();
+        if simulation.outcome() != Outcome::Playing {
+            outcome_tick = Some(simulation.tick());
+        }
+    }
+    // The first crab's death already puts the quota out of reach
+    assert_eq!(simulation.outcome(), Outcome::Lost);
+    assert!(outcome_tick.unwrap() < 10);
+    assert_eq!(simulation.entities().pending(), 1);
+}
+
+#[test]
+fn losing_a_king_from_a_hatch_loses_the_level() {
+    let mut level = Level::new(
+        Entities::new(vec![[1, 4]], vec![[0, -1]]).with_spawners(vec![Spawner::new(
+            [0, 0],
+            1,
+            1,
+            [0, 1],
+            Kind::King,
+        )]),
+        Map::new("H    \n   ^ \n#####\n  X  "),
+    );
+    level.rescue = 1;
+    let mut simulation = Simulation::new(level);
+    while simulation.outcome() == Outcome::Playing && simulation.tick() < 20 {
+        simulation.step();
+    }
+    assert_eq!(simulation.outcome(), Outcome::Lost);
+}
+
+#[test]
+fn spawners_round_trip_through_level_files() {
+    let source = r#"(
+    version: 2,
+    name: "Hatchery",
+    author: "Tester",
+    spawners: [(pos: (0, 1), count: 5, interval: 3, vel: (0, 1), kind: Floater)],
+    crabs: [],
+    layout: " H \n   \n X ",
+)"#;
+    let file: LevelFile = ron::de::from_str(source).unwrap();
+    let level = file.clone().into_level();
+    let spawners = level.entities.spawners();
+    assert_eq!(
+        spawners,
+        &[Spawner::new([0, 1], 5, 3, [0, 1], Kind::Floater)][..]
+    );
+    assert_eq!(level.rescue, 5);
+
+    let again = LevelFile::from_level(&level);
+    assert_eq!(again.spawners, file.spawners);
+    assert_eq!(again.rescue, None);
+}
+
+#[test]
+fn spawner_must_sit_on_a_hatch() {
+    let source = r#"(
+    version: 2,
+    name: "Hatchery",
+    author: "Tester",
+    spawners: [(pos: (1, 1), count: 1, interval: 0, vel: (0, 1))],
+    crabs: [],
+    layout: " H \n   \n X ",
+)"#;
+    let problems: Vec<String> = crabs::validate::validate_level(source)
+        .iter()
+        .map(|diagnostic| diagnostic.problem.to_string())
+        .collect();
+    assert!(problems.iter().any(|p| p.contains("not on a H hatch tile")));
+    assert!(problems
+        .iter()
+        .any(|p| p.contains("interval of at least 1")));
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+mod common;
+
+use common::crabs;
+use crabs::format::LevelFile;
+use crabs::{
+    Continuous, Edit, Entities, Event, Inventory, Level, Map, Scenery, Simulation, Teleporter,
+};
+
+fn simulation(
+    layout: &str,
+    crabs: Vec<([usize; 2], [isize; 2])>,
+    pads: Vec<[[usize; 2]; 2]>,
+) -> Simulation {
+    let mut level = common::level(layout, crabs);
+    level.map.teleporters = pads
+        .into_iter()
+        .enumerate()
+        .map(|(id, pads)| Teleporter { id, pads })
+        .collect();
+    Simulation::new(level)
+}
+
+#[test]
+fn teleporter_sends_crab_to_its_partner() {
+    let mut simulation = simulation(
+        "  T  \n#####\nT    \n#####",
+        vec![([0, 0], [0, 1])],
+        vec![[[0, 2], [2, 0]]],
+    );
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 1], [0, 1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([2, 0], [0, 1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([2, 1], [0, 1])]);
+}
+
+#[test]
+fn crab_carries_on_moving_after_teleporting() {
+    let mut simulation = simulation(
+        " T     \n#######\n     T \n#######",
+        vec![([0, 0], [0, 3])],
+        vec![[[0, 1], [2, 5]]],
+    );
+    simulation.step();
+    // One cell onto the pad, then two more from its partner (wrapping round the edge)
+    assert_eq!(crabs(&simulation), vec![([2, 0], [0, 3])]);
+}
+
+#[test]
+fn crabs_cannot_teleport_straight_back() {
+    let mut simulation = simulation(
+        "#T T#\n#####",
+        vec![([0, 2], [0, 1])],
+        vec![[[0, 1], [0, 3]]],
+    );
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 1], [0, 1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 2], [0, 1])]);
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 3], [0, 1])]);
+}
+
+#[test]
+fn crab_on_the_partner_pad_blocks_the_teleporter() {
+    let mut simulation = simulation(
+        " T T\n####",
+        vec![([0, 0], [0, 1]), ([0, 3], [0, 0])],
+        vec![[[0, 1], [0, 3]]],
+    );
+    simulation.step();
+    assert_eq!(crabs(&simulation), vec![([0, 1], [0, 1]), ([0, 3], [0, 0])]);
+}
+
+#[test]
+fn teleporters_work_under_continuous_physics() {
+    let mut map = Map::new("  T  \n#####\nT    \n#####");
+    map.physics.continuous = Some(Continuous::default());
+    map.teleporters = vec![Teleporter {
+        id: 0,
+        pads: [[0, 2], [2, 0]],
+    }];
+    let mut simulation =
+        Simulation::new(Level::new(Entities::new(vec![[0, 0]], vec![[0, 1]]), map));
+    for _ in 0..2 {
+        simulation.step();
+    }
+    let crab = simulation.entities().iter().next().unwrap();
+    assert_eq!(crab.position(), [2, 0]);
+    assert!(crab.speed()[1] > 0);
+}
+
+#[test]
+fn placed_teleporters_pair_up_in_order() {
+    let mut map = Map::new("    \n####");
+    map.inventory = Inventory::limited(HashMap::from([(Scenery::Teleporter, 3)]));
+    let mut simulation = Simulation::new(Level::new(Entities::new(Vec::new(), Vec::new()), map));
+    for x in [3, 0, 1].iter() {
+        let event = simulation.apply_edit(Edit::Place {
+            position: [0, *x],
+            scenery: Scenery::Teleporter,
+        });
+        assert!(matches!(event, Event::Placed { .. }));
+    }
+    assert_eq!(simulation.map().partner([0, 3]), Some([0, 0]));
+    assert_eq!(simulation.map().partner([0, 0]), Some([0, 3]));
+    assert_eq!(simulation.map().partner([0, 1]), None);
+}
+
+#[test]
+fn teleporters_round_trip_through_level_files() {
+    let source = r#"(
+    version: 2,
+    crabs: [(pos: (0, 0), vel: (0, 1))],
+    teleporters: [(id: 7, pads: ((0, 2), (1, 4)))],
+    layout: "  T X\n####T\n#####",
+)"#;
+    assert!(crabs::validate::validate_level(source).is_empty());
+    let file = LevelFile::parse(source).unwrap();
+    let level = file.clone().into_level();
+    assert_eq!(
+        level.map.teleporters,
+        vec![Teleporter {
+            id: 7,
+            pads: [[0, 2], [1, 4]]
+        }]
+    );
+    assert_eq!(LevelFile::from_level(&level).teleporters, file.teleporters);
+}
+
+#[test]
+fn teleporter_tiles_must_be_paired_once() {
+    let source = r#"(
+    version: 2,
+    crabs: [],
+    teleporters: [(id: 1, pads: ((0, 0), (0, 1))), (id: 1, pads: ((0, 1), (0, 3)))],
+    layout: "TT T X\n######",
+)"#;
+    let problems: Vec<String> = crabs::validate::validate_level(source)
+        .iter()
+        .map(|diagnostic| diagnostic.problem.to_string())
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            "teleporter ID 1 is used more than once",
+            "T tile at [0, 1] needs to be in exactly one teleporter pair",
+        ]
+    );
+}
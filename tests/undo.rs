@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+mod common;
+
+use common::glyph;
+use crabs::replay::{Keystroke, Session};
+use crabs::{Edit, Entities, Event, Inventory, Level, Map, Refusal, Scenery, Simulation};
+
+fn simulation(layout: &str) -> Simulation {
+    let mut level = common::level(layout, Vec::new());
+    level.map.inventory = Inventory::limited(HashMap::from([
+        (Scenery::Block, 1),
+        (Scenery::Teleporter, 3),
+    ]));
+    Simulation::new(level)
+}
+
+fn place(position: [usize; 2], scenery: Scenery) -> Edit {
+    Edit::Place { position, scenery }
+}
+
+#[test]
+fn undo_takes_back_a_placement_and_redo_puts_it_back() {
+    let mut simulation = simulation("     \n#####");
+    simulation.apply_edit(place([0, 2], Scenery::Block));
+    assert_eq!(simulation.placements(), 1);
+    assert_eq!(
+        simulation.map().inventory.remaining(Scenery::Block),
+        Some(0)
+    );
+
+    assert_eq!(
+        simulation.apply_edit(Edit::Undo),
+        Event::Removed {
+            position: [0, 2],
+            scenery: Scenery::Block
+        }
+    );
+    assert_eq!(glyph(simulation.map(), [0, 2]), ' ');
+    assert_eq!(simulation.placements(), 0);
+    assert_eq!(
+        simulation.map().inventory.remaining(Scenery::Block),
+        Some(1)
+    );
+
+    assert_eq!(
+        simulation.apply_edit(Edit::Redo),
+        Event::Placed {
+            position: [0, 2],
+            scenery: Scenery::Block
+        }
+    );
+    assert_eq!(glyph(simulation.map(), [0, 2]), '#');
+    assert_eq!(simulation.placements(), 1);
+}
+
+#[test]
+fn nothing_to_undo_or_redo_changes_nothing() {
+    let mut simulation = simulation("     \n#####");
+    assert_eq!(simulation.apply_edit(Edit::Undo), Event::Unchanged);
+    assert_eq!(simulation.apply_edit(Edit::Redo), Event::Unchanged);
+
+    // A new placement rules out redoing what was undone
+    simulation.apply_edit(place([0, 2], Scenery::Block));
+    simulation.apply_edit(Edit::Undo);
+    simulation.apply_edit(place([0, 3], Scenery::Block));
+    assert_eq!(simulation.apply_edit(Edit::Redo), Event::Unchanged);
+    assert_eq!(glyph(simulation.map(), [0, 2]), ' ');
+}
+
+#[test]
+fn only_placed_scenery_can_be_removed() {
+    let mut simulation = simulation("     \n#####");
+    assert_eq!(
+        simulation.apply_edit(Edit::Remove { position: [1, 2] }),
+        Event::Rejected {
+            position: [1, 2],
+            scenery: Scenery::Block,
+            reason: Refusal::NotPlaced
+        }
+    );
+    assert_eq!(glyph(simulation.map(), [1, 2]), '#');
+
+    simulation.apply_edit(place([0, 2], Scenery::Block));
+    assert!(matches!(
+        simulation.apply_edit(Edit::Remove { position: [0, 2] }),
+        Event::Removed { .. }
+    ));
+    assert_eq!(
+        simulation.map().inventory.remaining(Scenery::Block),
+        Some(1)
+    );
+
+    // Removals can be undone too
+    simulation.apply_edit(Edit::Undo);
+    assert_eq!(glyph(simulation.map(), [0, 2]), '#');
+    assert_eq!(
+        simulation.map().inventory.remaining(Scenery::Block),
+        Some(0)
+    );
+}
+
+#[test]
+fn removing_a_pad_leaves_its_partner_waiting() {
+    let mut simulation = simulation("     \n#####");
+    for x in [0, 2, 4].iter() {
+        simulation.apply_edit(place([0, *x], Scenery::Teleporter));
+    }
+    assert_eq!(simulation.map().partner([0, 0]), Some([0, 2]));
+
+    // The partner pairs straight up with the pad that was already waiting
+    simulation.apply_edit(Edit::Remove { position: [0, 2] });
+    assert_eq!(simulation.map().partner([0, 2]), None);
+    assert_eq!(simulation.map().partner([0, 0]), Some([0, 4]));
+
+    simulation.apply_edit(Edit::Undo);
+    assert_eq!(simulation.map().partner([0, 2]), None);
+    simulation.apply_edit(Edit::Remove { position: [0, 4] });
+    assert_eq!(simulation.map().partner([0, 0]), Some([0, 2]));
+}
+
+#[test]
+fn keys_undo_redo_and_delete() {
+    let mut map = Map::new("     \n     \n#####");
+    map.inventory = Inventory::unlimited();
+    let mut session = Session::new(Level::new(Entities::new(Vec::new(), Vec::new()), map), None);
+    let cursor = session.cursor();
+    session.press(Key::Char('#'));
+    session.press(Key::Char('u'));
+    assert_eq!(glyph(session.simulation().map(), cursor), ' ');
+    session.press(Key::Ctrl('r'));
+    assert_eq!(glyph(session.simulation().map(), cursor), '#');
+    session.press(Key::Delete);
+    assert_eq!(glyph(session.simulation().map(), cursor), ' ');
+
+    // Both keys are kept in recordings
+    for key in [Key::Ctrl('r'), Key::Delete].iter() {
+        assert_eq!(Keystroke::from_key(*key).map(Keystroke::to_key), Some(*key));
+    }
+}
@@ -0,0 +1,168 @@
+use crabs::format::LevelFile;
+use crabs::validate::{validate_level, Problem};
+use crabs::Scenery;
+
+// Line, column and problem of each diagnostic
+fn problems(source: &str) -> Vec<(usize, usize, Problem)> {
+    validate_level(source)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.problem))
+        .collect()
+}
+
+#[test]
+fn crabs_and_hatches_cannot_start_faster_than_terminal_velocity() {
+    let source = r#"(
+    version: 2,
+    crabs: [(pos: (0, 0), vel: (0, 1)), (pos: (0, 1), vel: (0, 1000000))],
+    spawners: [(pos: (0, 2), count: 1, interval: 1, vel: (-11, 0))],
+    layout: "  HX\n####",
+)"#;
+    assert_eq!(
+        problems(source),
+        [
+            (
+                3,
+                41,
+                Problem::CrabTooFast {
+                    crab: 1,
+                    velocity: [0, 1000000],
+                }
+            ),
+            (
+                4,
+                16,
+                Problem::SpawnerTooFast {
+                    spawner: 0,
+                    velocity: [-11, 0],
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn quotas_cannot_need_more_crabs_than_the_level_has() {
+    let source = r#"(
+    version: 2,
+    rescue: Some(4),
+    crabs: [(pos: (0, 0), vel: (0, 1))],
+    spawners: [(pos: (0, 2), count: 2, interval: 1, vel: (0, 1))],
+    layout: "  HX\n####",
+)"#;
+    assert_eq!(
+        problems(source),
+        [(
+            3,
+            5,
+            Problem::QuotaTooHigh {
+                rescue: 4,
+                crabs: 3
+            }
+        )]
+    );
+    assert!(problems(&source.replace("Some(4)", "Some(3)")).is_empty());
+}
+
+#[test]
+fn inventories_only_hold_placeable_scenery() {
+    let source = r#"(
+    version: 2,
+    inventory: Some({'#': 1, 'Q': 2, 'X': 1}),
+    crabs: [(pos: (0, 0), vel: (0, 1))],
+    layout: "  X\n###",
+)"#;
+    assert_eq!(
+        problems(source),
+        [
+            (3, 30, Problem::Unplaceable { glyph: 'Q' }),
+            (3, 38, Problem::Unplaceable { glyph: 'X' }),
+        ]
+    );
+
+    // Anything else is left out of the budget rather than becoming something else
+    let level = LevelFile::parse(source).unwrap().into_level();
+    assert_eq!(level.map.inventory.remaining(Scenery::Empty), Some(0));
+    assert_eq!(level.map.inventory.remaining(Scenery::Safety), Some(0));
+    assert_eq!(level.map.inventory.remaining(Scenery::Block), Some(1));
+}
+
+#[test]
+fn layouts_must_be_rectangles_of_known_glyphs() {
+    let source = "(version: 2, crabs: [], layout: \"  X\\n#\\n#Q#\")";
+    assert_eq!(
+        problems(source),
+        [
+            (
+                1,
+                39,
+                Problem::RaggedRow {
+                    row: 1,
+                    expected: 3,
+                    found: 1,
+                }
+            ),
+            (1, 43, Problem::UnknownGlyph { glyph: 'Q' }),
+        ]
+    );
+}
+
+#[test]
+fn levels_need_somewhere_for_the_crabs_to_go() {
+    let source = "(version: 2, crabs: [],\n    layout: \"   \\n###\")";
+    assert_eq!(problems(source), [(2, 5, Problem::NoSafety)]);
+}
+
+#[test]
+fn crabs_must_start_in_open_cells_on_the_map() {
+    let source = r#"(
+    version: 2,
+    crabs: [
+        (pos: (0, 0), vel: (0, 1)),
+        (pos: (5, 0), vel: (0, 1)),
+        (pos: (1, 1), vel: (0, 1)),
+    ],
+    layout: "  X\n###",
+)"#;
+    assert_eq!(
+        problems(source),
+        [
+            (
+                5,
+                9,
+                Problem::CrabOutOfBounds {
+                    crab: 1,
+                    position: [5, 0],
+                }
+            ),
+            (
+                6,
+                9,
+                Problem::CrabInScenery {
+                    crab: 2,
+                    scenery: Scenery::Block,
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn crab_ids_are_not_reused() {
+    let source = r#"(
+    version: 2,
+    crabs: [(id: Some(3), pos: (0, 0), vel: (0, 1)), (id: Some(3), pos: (0, 1), vel: (0, 1))],
+    layout: "  X\n###",
+)"#;
+    assert_eq!(
+        problems(source),
+        [(3, 54, Problem::DuplicateCrab { id: 3 })]
+    );
+
+    // Legacy files would quietly keep the last one
+    let legacy = "(\n    x: {0: 0, 0: 1},\n    y: {0: 0},\n    vx: {0: 1},\n    vy: {0: 0},\n    layout: \"  X\\n###\",\n)";
+    assert_eq!(
+        problems(legacy),
+        [(2, 15, Problem::DuplicateCrab { id: 0 })]
+    );
+}